@@ -1,10 +1,178 @@
 extern crate serde;
+extern crate sha2;
 
-use std::collections::HashMap;
-use crate::Problem;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Component, Path};
+use crate::{Problem, Language, LanguageSettings};
+use crate::polygon;
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the arena file inside a bundle
+const BUNDLE_ARENA: &str = "arena.json";
+/// Directory, inside a bundle, with the test data stored out of line
+const BUNDLE_DATA: &str = "data";
+/// Size, in bytes, from which inline inputs and outputs are moved out of line in a bundle
+const OUT_OF_LINE_SIZE: usize = 64 * 1024;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Arena {
-    pub problems: HashMap<String, Problem>
-}
\ No newline at end of file
+    pub problems: HashMap<String, Problem>,
+    /// Toolchain the solutions were built with, which is also used to grade the competitors
+    #[serde(default)]
+    pub language_settings: HashMap<Language, LanguageSettings>,
+    /// Sha256 of every file of a bundled arena, by its path inside the bundle
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub manifest: BTreeMap<String, String>
+}
+
+impl Arena {
+    /// Reads an arena file, or the directory of a bundle, whose files are checked against the manifest. Input and
+    /// output files of the scenarios are resolved relative to the arena, and have to be in the manifest.
+    pub fn load(path: &Path) -> io::Result<Arena> {
+        let (base, file) = if path.is_dir() {
+            (path.to_path_buf(), path.join(BUNDLE_ARENA))
+        } else {
+            (path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf(), path.to_path_buf())
+        };
+        let mut arena: Arena = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+
+        for (name, checksum) in arena.manifest.iter() {
+            let mut hasher = Sha256::new();
+            io::copy(&mut std::fs::File::open(base.join(inside(name)?))?, &mut hasher)?;
+            if hex(&hasher.finalize()) != *checksum {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("\"{}\" does not match its checksum in the manifest", name)));
+            }
+        }
+
+        for scenario in arena.problems.values_mut().flat_map(|problem| problem.scenarios.iter_mut()) {
+            for file in [&mut scenario.input_file, &mut scenario.output_file].into_iter().flatten() {
+                // Only files with a checksum are trusted
+                if !arena.manifest.contains_key(file.as_str()) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("\"{}\" is not in the manifest", file)));
+                }
+                *file = base.join(inside(file)?).to_string_lossy().into_owned();
+            }
+        }
+        Ok(arena)
+    }
+
+    /// Writes the arena as a bundle in the directory: test data read from files, or too large to be inline, is
+    /// stored in its own files, named after their sha256, next to the arena file
+    pub fn bundle(&self, dir: &Path) -> io::Result<()> {
+        let mut arena = self.clone();
+        arena.manifest.clear();
+        std::fs::create_dir_all(dir.join(BUNDLE_DATA))?;
+
+        for scenario in arena.problems.values_mut().flat_map(|problem| problem.scenarios.iter_mut()) {
+            for (inline, file) in [(&mut scenario.input, &mut scenario.input_file), (&mut scenario.output, &mut scenario.output_file)] {
+                let content = match (file.as_ref(), inline.as_ref()) {
+                    (Some(file), _) => std::fs::read(file)?,
+                    (None, Some(text)) if text.len() >= OUT_OF_LINE_SIZE => text.as_bytes().to_vec(),
+                    _ => continue
+                };
+                let checksum = hex(&Sha256::digest(&content));
+                let name = format!("{}/{}", BUNDLE_DATA, checksum);
+                std::fs::write(dir.join(&name), content)?;
+                arena.manifest.insert(name.clone(), checksum);
+                *inline = None;
+                *file = Some(name);
+            }
+        }
+
+        std::fs::write(dir.join(BUNDLE_ARENA), serde_json::to_string_pretty(&arena)?)
+    }
+
+    /// Moves the content of every input and output file of the scenarios into the arena, which has to be text
+    pub fn inline(mut self) -> io::Result<Arena> {
+        for scenario in self.problems.values_mut().flat_map(|problem| problem.scenarios.iter_mut()) {
+            for (inline, file) in [(&mut scenario.input, &mut scenario.input_file), (&mut scenario.output, &mut scenario.output_file)] {
+                if let Some(path) = file.take() {
+                    let content = String::from_utf8(std::fs::read(&path)?).map_err(|_e| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("\"{}\" is not text, so it can only be stored in a bundle", path))
+                    })?;
+                    *inline = Some(content);
+                }
+            }
+        }
+        self.manifest.clear();
+        Ok(self)
+    }
+
+    /// Writes every problem as a Polygon package, in its own directory inside `dir`
+    pub fn polygon(&self, dir: &Path) -> io::Result<()> {
+        polygon::export(self, dir)
+    }
+}
+
+/// Checks that a path of the arena stays inside of it
+fn inside(name: &str) -> io::Result<&Path> {
+    let path = Path::new(name);
+    if path.components().all(|component| matches!(component, Component::Normal(_))) {
+        Ok(path)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, format!("\"{}\" is not a path inside the arena", name)))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn arena(output_file: &Path) -> Arena {
+        let input = "1\n".repeat(OUT_OF_LINE_SIZE);
+        serde_json::from_value(serde_json::json!({
+            "problems": {"sum": {
+                "scenarios": [{"input": input, "output_file": output_file, "max_time": 1.0, "max_ram": null, "points": 1}],
+                "filename": "sum",
+                "language": null,
+                "points": 1
+            }}
+        })).unwrap()
+    }
+
+    #[test]
+    fn bundle_round_trip() {
+        let dir = TempDir::new("arena").unwrap();
+        let output = dir.path().join("sum.out");
+        std::fs::write(&output, "65536\n").unwrap();
+        let bundle = dir.path().join("bundle");
+        arena(&output).bundle(&bundle).unwrap();
+
+        let loaded = Arena::load(&bundle).unwrap();
+        assert_eq!(loaded.manifest.len(), 2);
+        let scenario = &loaded.problems["sum"].scenarios[0];
+        assert!(scenario.input.is_none() && scenario.output.is_none());
+        let input = std::fs::read_to_string(scenario.input_file.as_ref().unwrap()).unwrap();
+        assert_eq!(input.len(), 2 * OUT_OF_LINE_SIZE);
+        assert_eq!(std::fs::read_to_string(scenario.output_file.as_ref().unwrap()).unwrap(), "65536\n");
+    }
+
+    #[test]
+    fn load_checks_the_manifest() {
+        let dir = TempDir::new("arena").unwrap();
+        let output = dir.path().join("sum.out");
+        std::fs::write(&output, "65536\n").unwrap();
+        let bundle = dir.path().join("bundle");
+        arena(&output).bundle(&bundle).unwrap();
+
+        let checksum = hex(&Sha256::digest(b"65536\n"));
+        std::fs::write(bundle.join(BUNDLE_DATA).join(&checksum), "65535\n").unwrap();
+        let error = Arena::load(&bundle).err().unwrap();
+        assert!(error.to_string().contains("does not match its checksum"));
+
+        // Files of the scenarios without a checksum are refused
+        std::fs::write(bundle.join(BUNDLE_DATA).join(&checksum), "65536\n").unwrap();
+        let mut unlisted: Arena = serde_json::from_str(&std::fs::read_to_string(bundle.join(BUNDLE_ARENA)).unwrap()).unwrap();
+        unlisted.manifest.remove(&format!("{}/{}", BUNDLE_DATA, checksum));
+        std::fs::write(bundle.join(BUNDLE_ARENA), serde_json::to_string(&unlisted).unwrap()).unwrap();
+        let error = Arena::load(&bundle).err().unwrap();
+        assert!(error.to_string().contains("is not in the manifest"));
+    }
+}
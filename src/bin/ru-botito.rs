@@ -5,35 +5,148 @@ extern crate lazy_static;
 extern crate log;
 
 use clap::{Parser};
-use tito::{Tito, SimpleLogger, Competitor, Settings, Arena, Evaluation};
+use tito::{Tito, SimpleLogger, Competitor, Settings, Arena, Evaluation, Tournament, Cache, Sandbox};
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 enum Args {
     #[clap(about = "build subcommand to precompute the answers to the problems")]
     Build(BuildArgs),
     #[clap(about = "run subcommand for executing the robot")]
-    Run(RunArgs)
+    Run(RunArgs),
+    #[clap(about = "tournament subcommand for making the bots of the competitors play each other")]
+    Tournament(TournamentArgs),
+    #[clap(about = "export subcommand for writing the problems of an arena in the format of other tools")]
+    Export(ExportArgs)
 }
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct BuildArgs {
-   #[clap(long, help = "path to the location of the problem configuration file")]
+   #[clap(long, help = "path to the location of the problem configuration file, or of a course directory")]
    settings: Option<String>,
    #[clap(long, help = "generates a very basic example config and a very basic shell problem")]
-   example_config: bool
+   example_config: bool,
+   #[clap(long, help = "writes the arena as a directory, with large test data in files of its own")]
+   bundle: Option<String>,
+   #[clap(flatten)]
+   cache: CacheArgs
 }
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct RunArgs {
-   #[clap(long, help = "path to the location of the arena file")]
+   #[clap(long, help = "path to the location of the arena file, or of the directory of a bundle")]
    arena: String,
    #[clap(long, help = "competitor list")]
-   competitor: Vec<String>
+   competitor: Vec<String>,
+   #[clap(long, default_value_t = 1, help = "number of competitor files graded at the same time")]
+   workers: usize,
+   #[clap(long, help = "keeps every worker in its own cpu, for steadier time measurements")]
+   pin_cpus: bool,
+   #[clap(flatten)]
+   cache: CacheArgs,
+   #[clap(flatten)]
+   sandbox: SandboxArgs
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct TournamentArgs {
+   #[clap(long, help = "path to the location of the tournament configuration file")]
+   settings: Option<String>,
+   #[clap(long, help = "generates a very basic example tournament config")]
+   example_config: bool,
+   #[clap(long, help = "directory where the log of every match is written")]
+   logs: Option<String>,
+   #[clap(long, help = "competitor list")]
+   competitor: Vec<String>,
+   #[clap(flatten)]
+   cache: CacheArgs,
+   #[clap(flatten)]
+   sandbox: SandboxArgs
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct ExportArgs {
+   #[clap(long, help = "path to the location of the arena file, or of the directory of a bundle")]
+   arena: String,
+   #[clap(long, help = "directory where every problem is written as a Polygon package")]
+   polygon: String
+}
+
+#[derive(Parser, Debug)]
+struct CacheArgs {
+   #[clap(long, help = "compiles everything again, without reading or writing the compilation cache")]
+   no_cache: bool,
+   #[clap(long, help = "removes every stored compilation before starting")]
+   clear_cache: bool,
+   #[clap(long, help = "directory of the compilation cache, ~/.cache/tito by default")]
+   cache_dir: Option<String>
+}
+
+#[derive(Parser, Debug)]
+struct SandboxArgs {
+   #[clap(long, help = "runs the programs of the competitors without network and with a read-only filesystem, except for their working directory (linux only)")]
+   sandbox: bool,
+   #[clap(long, requires = "sandbox", help = "maximum number of processes a sandboxed program can have running at once")]
+   max_processes: Option<u64>
+}
+
+impl SandboxArgs {
+    fn sandbox(&self) -> Option<Sandbox> {
+        if self.sandbox {
+            Some(Sandbox{max_processes: self.max_processes})
+        } else {
+            None
+        }
+    }
+}
+
+/// Prepares the compilation cache described by the arguments, if any
+fn cache(args: &CacheArgs) -> Option<Cache> {
+    let cache = match args.cache_dir.as_ref().map(PathBuf::from).or_else(Cache::default_dir) {
+        Some(dir) => Cache::new(dir),
+        None => {
+            log::warn!("No directory for the compilation cache was found, it will not be used");
+            return None;
+        }
+    };
+    if args.clear_cache {
+        match cache.clear() {
+            Ok(_) => log::info!("Compilation cache at {} cleared", cache.dir().display()),
+            Err(e) => log::warn!("Could not clear the compilation cache, {}", e)
+        }
+    }
+    if args.no_cache {
+        None
+    } else {
+        Some(cache)
+    }
+}
+
+/// Reads competitors described as id:path_to_files:path_for_result
+fn parse_competitors(descriptions: &[String]) -> Result<Vec<Competitor>, &'static str> {
+    descriptions.iter().map(|competitor_string| {
+        let tokens: Vec<&str> = competitor_string.split(':').collect();
+        if tokens.len() != 3 {
+            return Err("Competitors must be described as id:path_to_files:path_for_result");
+        }
+        if tokens[0].is_empty() {
+            return Err("Every competitor needs an id.");
+        }
+        if tokens[1].is_empty() {
+            return Err("Every competitor needs a path to the files");
+        }
+        Ok(Competitor {
+            id: tokens[0].into(),
+            files: tokens[1].into(),
+            result: if tokens[2].is_empty() { None } else { Some(tokens[2].into()) }
+        })
+    }).collect()
 }
 
 fn main() {
@@ -56,7 +169,7 @@ fn main() {
                                     }
                                 }
                                 // Now we write the example problem
-                                match File::create("problem-a.sh".to_string()) {
+                                match File::create("problem-a.sh") {
                                     Ok(mut f) => {
                                         match f.write_all(b"echo \"Hello, ${1}!\"\n") {
                                             Ok(_) => log::info!("example poblem written to `problem-a.sh`"),
@@ -81,32 +194,9 @@ fn main() {
                 }
             } else {
                 let settings_path = build_args.settings.unwrap_or("./settings.json".to_string());
-                // We check if there are indeed settings in the path
-                let settings: Settings = match File::open(&settings_path) {
-                    Ok(mut f) => {
-                        let mut content = String::new();
-                        match f.read_to_string(&mut content) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                log::error!("{}", e);
-                                return;
-                            }
-                        }
-                        let value: serde_json::Value =  match serde_json::from_str(&content) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                log::error!("Could not load json from file, {}", e);
-                                return;
-                            }
-                        };
-                        match serde_json::from_value(value) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                log::error!("Could not load settings, {}", e);
-                                return;
-                            }
-                        }
-                    },
+                // Settings are either a file or a course directory
+                let settings = match Settings::load(Path::new(&settings_path)) {
+                    Ok(v) => v,
                     Err(e) => {
                         log::error!("Could not load settings, {}", e);
                         return;
@@ -114,14 +204,19 @@ fn main() {
                 };
     
                 let mut t = match Tito::new() {
-                    Ok(v) => v,
+                    Ok(v) => v.with_cache(cache(&build_args.cache)),
                     Err(e) => {
                         log::error!("{}", e);
                         return;
                     }
                 };
     
-                let arena = match t.build(settings) {
+                // Fixtures are found next to the settings, or in the course directory
+                let base = match Path::new(&settings_path) {
+                    path if path.is_dir() => path,
+                    path => path.parent().unwrap_or_else(|| Path::new("."))
+                };
+                let arena = match t.build(settings, base) {
                     Ok(v) => v,
                     Err(e) => {
                         log::error!("{}", e);
@@ -129,6 +224,23 @@ fn main() {
                     }
                 };
     
+                if let Some(bundle) = build_args.bundle {
+                    match arena.bundle(Path::new(&bundle)) {
+                        Ok(_) => log::info!("Arena saved to {}", bundle),
+                        Err(e) => log::error!("Could not write the bundle, {}", e)
+                    }
+                    return;
+                }
+
+                // Without a bundle, test data files are moved into the arena
+                let arena = match arena.inline() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return;
+                    }
+                };
+
                 // We write the arena to an arena.json file
                 match serde_json::to_string_pretty(&arena) {
                     Ok(v) => match File::create("arena.json") {
@@ -155,23 +267,7 @@ fn main() {
             }
         },
         Args::Run(run_args) => {
-            let competitors: Vec<_> = match run_args.competitor.iter().map(|competitor_string| {
-                let tokens: Vec<&str> = competitor_string.split(":").collect();
-                if tokens.len() != 3 {
-                    return Err("Competitors must be described as id:path_to_files:path_for_result");
-                }
-                if tokens[0].len() == 0 {
-                    return Err("Every competitor needs an id.");
-                }
-                if tokens[1].len() == 0 {
-                    return Err("Every competitor needs a path to the files");
-                }
-                Ok(Competitor {
-                    id: tokens[0].into(),
-                    files: tokens[1].into(),
-                    result: if tokens[2].len() == 0 { None } else { Some(tokens[2].into()) }
-                })
-            }).collect::<Result<_, _>>() {
+            let competitors = match parse_competitors(&run_args.competitor) {
                 Ok(v) => v,
                 Err(e) => {
                     log::error!("{}", e);
@@ -180,33 +276,17 @@ fn main() {
             };
             
             let mut tito = match Tito::new() {
-                Ok(v) => v,
+                Ok(v) => v.with_workers(run_args.workers).with_cpu_pinning(run_args.pin_cpus).with_cache(cache(&run_args.cache)).with_sandbox(run_args.sandbox.sandbox()),
                 Err(e) => {
                     log::error!("{}", e);
                     return;
                 }
             };
 
-            let arena: Arena = match File::open(run_args.arena) {
-                Ok(mut f) => {
-                    let mut content = String::new();
-                    match f.read_to_string(&mut content) {
-                        Ok(_) => (),
-                        Err(e) => {
-                            log::error!("{}", e);
-                            return;
-                        }
-                    }
-                    match serde_json::from_str(&content) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            log::error!("{}", e);
-                            return;
-                        }
-                    }
-                },
+            let arena = match Arena::load(Path::new(&run_args.arena)) {
+                Ok(v) => v,
                 Err(e) => {
-                    log::error!("{}", e);
+                    log::error!("Could not load the arena, {}", e);
                     return;
                 }
             };
@@ -245,8 +325,25 @@ fn main() {
                                 for (p_name, evaluation) in grades.iter() {
                                     if let Some(problem) = arena.problems.get(p_name) {
                                         match evaluation {
-                                            Evaluation::Grade{score} => {
+                                            Evaluation::Grade{score, scenarios} => {
                                                 report += &format!("-> problem \"{}\": {}\n", p_name, score*10.0);
+                                                for (idx, result) in scenarios.iter().enumerate() {
+                                                    report += &format!("   scenario {}: {} ({:.3} s", idx + 1, result.verdict, result.wall_time);
+                                                    if let Some(cpu_time) = result.cpu_time {
+                                                        report += &format!(", {:.3} s of cpu", cpu_time);
+                                                    }
+                                                    report += ")";
+                                                    if let Some(message) = &result.message {
+                                                        report += &format!(", {}", message.lines().next().unwrap_or(""));
+                                                    }
+                                                    report += "\n";
+                                                }
+                                            },
+                                            Evaluation::CompileError{stderr} => {
+                                                report += &format!("-> problem \"{}\": compilation error\n", p_name);
+                                                for line in stderr.lines() {
+                                                    report += &format!("   | {}\n", line);
+                                                }
                                             },
                                             Evaluation::RunError => {
                                                 report += &format!("-> problem \"{}\": execution/compilation error\n", p_name);
@@ -279,10 +376,157 @@ fn main() {
                         }
                     }
                 },
+                Err(e) => {
+                    log::error!("{}", e);
+                }
+            }
+        },
+        Args::Tournament(tournament_args) => {
+            if tournament_args.example_config {
+                match serde_json::to_string_pretty(&Tournament::example()) {
+                    Ok(content) => match File::create("./tournament.json") {
+                        Ok(mut f) => match f.write_all(content.as_bytes()) {
+                            Ok(_) => log::info!("example tournament written to `tournament.json`"),
+                            Err(e) => log::error!("{}", e)
+                        },
+                        Err(e) => log::error!("{}", e)
+                    },
+                    Err(e) => log::error!("{}", e)
+                }
+                return;
+            }
+
+            let competitors = match parse_competitors(&tournament_args.competitor) {
+                Ok(v) => v,
                 Err(e) => {
                     log::error!("{}", e);
                     return;
                 }
+            };
+
+            let settings_path = tournament_args.settings.unwrap_or("./tournament.json".to_string());
+            let tournament: Tournament = match std::fs::read_to_string(&settings_path) {
+                Ok(content) => match serde_json::from_str(&content) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::error!("Could not load tournament, {}", e);
+                        return;
+                    }
+                },
+                Err(e) => {
+                    log::error!("Could not load tournament, {}", e);
+                    return;
+                }
+            };
+            let logs = PathBuf::from(tournament_args.logs.unwrap_or("./matches".to_string()));
+
+            let mut tito = match Tito::new() {
+                Ok(v) => v.with_cache(cache(&tournament_args.cache)).with_sandbox(tournament_args.sandbox.sandbox()),
+                Err(e) => {
+                    log::error!("{}", e);
+                    return;
+                }
+            };
+
+            let results = match tito.tournament(competitors.clone(), tournament.clone(), &logs) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("{}", e);
+                    return;
+                }
+            };
+            match serde_json::to_string_pretty(&results) {
+                Ok(content) => match File::create("standings.json") {
+                    Ok(mut f) => match f.write_all(content.as_bytes()) {
+                        Ok(_) => log::info!("Standings saved to standings.json"),
+                        Err(e) => {
+                            log::error!("{}", e);
+                            return;
+                        }
+                    },
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return;
+                    }
+                },
+                Err(e) => {
+                    log::error!("{}", e);
+                    return;
+                }
+            }
+
+            for competitor in competitors {
+                let dir = match competitor.result {
+                    Some(dir) => dir,
+                    None => {
+                        log::info!("competitor {} will not receive standings", competitor.id);
+                        continue;
+                    }
+                };
+                log::info!("Adding standings to {}", competitor.id);
+                let mut report: String = "Beep boop! Your tournament results are here:\n\n".into();
+                if let Some(evaluation) = results.excluded.get(&competitor.id) {
+                    match evaluation {
+                        Evaluation::CompileError{stderr} => {
+                            report += "-> your bot did not play: compilation error\n";
+                            for line in stderr.lines() {
+                                report += &format!("   | {}\n", line);
+                            }
+                        },
+                        Evaluation::NoFile => {
+                            report += &format!("-> your bot did not play: file not found \"{}\"\n", tournament.filename);
+                        },
+                        _ => {
+                            report += "-> your bot did not play: execution/compilation error\n";
+                        }
+                    }
+                } else if let Some(position) = results.standings.iter().position(|standing| standing.id == competitor.id) {
+                    let standing = &results.standings[position];
+                    report += &format!("-> position {} of {}: {} points, rating {:.0} ({} won, {} drawn, {} lost)\n",
+                        position + 1, results.standings.len(), standing.points, standing.rating, standing.wins, standing.draws, standing.losses);
+                    for game in results.matches.iter().filter(|game| game.players.contains(&competitor.id)) {
+                        let outcome = match (&game.error, &game.log) {
+                            (_, None) => "bye".to_string(),
+                            (Some(error), _) => format!("void, {}", error),
+                            (None, _) => game.scores.iter().map(|score| score.to_string()).collect::<Vec<_>>().join(" - ")
+                        };
+                        report += &format!("   round {}: {}, {}", game.round, game.players.join(" vs "), outcome);
+                        // Logs are copied so that students can follow their matches, with only what they can read
+                        let seat = game.players.iter().position(|player| *player == competitor.id);
+                        if let (Some(log), Some(player_log)) = (&game.log, seat.and_then(|seat| game.player_logs.get(seat))) {
+                            let mut source = logs.clone();
+                            source.push(player_log);
+                            let mut destination = PathBuf::from(&dir);
+                            destination.push(log);
+                            match std::fs::copy(&source, &destination) {
+                                Ok(_) => report += &format!(" [{}]", log),
+                                Err(e) => log::warn!("Could not copy log \"{}\" for user \"{}\", {}", log, competitor.id, e)
+                            }
+                        }
+                        report += "\n";
+                    }
+                } else {
+                    log::error!("somehow, standings for competitor {} are not present", competitor.id);
+                    continue;
+                }
+                let mut result_file = PathBuf::from(dir);
+                result_file.push("tournament.txt");
+                if let Err(e) = std::fs::write(result_file, report) {
+                    log::warn!("Could not write report for user \"{}\", {}", competitor.id, e);
+                }
+            }
+        },
+        Args::Export(export_args) => {
+            let arena = match Arena::load(Path::new(&export_args.arena)) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Could not load the arena, {}", e);
+                    return;
+                }
+            };
+            match arena.polygon(Path::new(&export_args.polygon)) {
+                Ok(_) => log::info!("Polygon packages saved to {}", export_args.polygon),
+                Err(e) => log::error!("Could not write the Polygon packages, {}", e)
             }
         }
     }
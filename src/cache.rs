@@ -0,0 +1,231 @@
+//! Content addressed storage of compiled programs, so that identical sources skip the pre tools, and of the outputs
+//! of generators
+extern crate sha2;
+extern crate tempdir;
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tempdir::TempDir;
+use crate::LanguageSettings;
+
+/// Directory of the cache with the outputs of generators, apart from the compilations
+const OUTPUTS_DIR: &str = "outputs";
+
+/// Directory holding the results of previous compilations, one subdirectory per key
+#[derive(Clone)]
+pub struct Cache {
+    dir: PathBuf
+}
+
+impl Cache {
+    pub fn new<T: Into<PathBuf>>(dir: T) -> Cache {
+        Cache {
+            dir: dir.into()
+        }
+    }
+
+    /// Directory used when none is given, `$XDG_CACHE_HOME/tito` or `~/.cache/tito`
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let mut dir = PathBuf::from(std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?);
+                dir.push(".cache");
+                dir
+            }
+        };
+        Some(base.join("tito"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes every stored compilation
+    pub fn clear(&self) -> io::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    /// Identifies the compilation of a source with a toolchain, as the hash of both. Commands are hashed as they are
+    /// run, with the placeholders of the settings expanded and the file each tool runs, so that pointing a placeholder
+    /// to another compiler, or upgrading one, does not reuse what the old one built. The source file and the directory
+    /// of the compilation change every time, so they are left as placeholders.
+    pub fn key(source: &str, language_settings: &LanguageSettings) -> String {
+        let expand = |text: &str| language_settings.expand(text, "{filename}", "{pwd}");
+        let commands: Vec<_> = language_settings.pre_tools.iter().flatten().chain(std::iter::once(&language_settings.tool)).map(|tool| {
+            let utility = expand(&tool.utility);
+            // Temporal tools are built by the compilation, so there is nothing to look for before it
+            let file = if tool.temporal { None } else { executable(&utility) };
+            (tool.arguments.iter().map(|argument| expand(argument)).collect::<Vec<_>>(), utility, file)
+        }).collect();
+        // Variables are sorted, so that the key does not depend on the order of a map
+        let inherited: BTreeMap<_, _> = language_settings.inherit_env.iter()
+            .map(|name| (name, std::env::var_os(name).map(|value| value.to_string_lossy().into_owned())))
+            .collect();
+        let env: BTreeMap<_, _> = language_settings.env.iter().map(|(name, value)| (name, expand(value))).collect();
+        let toolchain = (commands, language_settings.source_file(), inherited, env);
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&toolchain).unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(source.as_bytes());
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Copies the files of a stored compilation into the directory, returning false if there is none
+    pub fn fetch(&self, key: &str, path: &Path) -> io::Result<bool> {
+        let entry = self.dir.join(key);
+        if !entry.is_dir() {
+            return Ok(false);
+        }
+        copy_dir(&entry, path)?;
+        Ok(true)
+    }
+
+    /// Stores the files of a finished compilation that took place in the directory
+    pub fn store(&self, key: &str, path: &Path) -> io::Result<()> {
+        let entry = self.dir.join(key);
+        if entry.is_dir() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+
+        // The entry is renamed into place once complete, so that concurrent runs never see half of it
+        let staging = TempDir::new_in(&self.dir, "staging")?;
+        let files = staging.path().join("files");
+        copy_dir(path, &files)?;
+        if let Err(e) = std::fs::rename(&files, &entry) {
+            // Someone else might have stored the same compilation meanwhile
+            if !entry.is_dir() {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Identifies the output of a compiled program run with the arguments
+    pub fn output_key(compilation: &str, arguments: &[String]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(compilation.as_bytes());
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(arguments).unwrap_or_default());
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Reads a stored output, returning None if there is none
+    pub fn fetch_output(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.dir.join(OUTPUTS_DIR).join(key)) {
+            Ok(output) => Ok(Some(output)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Stores the output of a program
+    pub fn store_output(&self, key: &str, output: &[u8]) -> io::Result<()> {
+        let outputs = self.dir.join(OUTPUTS_DIR);
+        std::fs::create_dir_all(&outputs)?;
+
+        // Like compilations, outputs are renamed into place once written
+        let staging = TempDir::new_in(&self.dir, "staging")?;
+        let file = staging.path().join("output");
+        std::fs::write(&file, output)?;
+        std::fs::rename(&file, outputs.join(key))
+    }
+}
+
+/// Path, size and modification time, in nanoseconds, of the file a utility runs, when it can be found
+fn executable(utility: &str) -> Option<(PathBuf, u64, u128)> {
+    let path = if utility.contains(std::path::MAIN_SEPARATOR) || utility.contains('/') {
+        PathBuf::from(utility)
+    } else {
+        std::env::split_paths(&std::env::var_os("PATH")?).map(|dir| dir.join(utility)).find(|path| path.is_file())?
+    };
+    // Links are resolved, as tools like `g++` often point to the version that is installed
+    let path = path.canonicalize().ok()?;
+    let metadata = std::fs::metadata(&path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((path, metadata.len(), modified))
+}
+
+/// Copies the contents of a directory into another one, which is created if needed
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for item in std::fs::read_dir(from)? {
+        let item = item?;
+        let target = to.join(item.file_name());
+        if item.file_type()?.is_dir() {
+            copy_dir(&item.path(), &target)?;
+        } else {
+            std::fs::copy(item.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tool;
+
+    fn settings(utility: &str, placeholders: &[(&str, &str)]) -> LanguageSettings {
+        let compile = Tool::new(utility, false, vec!["{flags}".to_string(), "{filename}".to_string(), "-o".to_string(), "{pwd}/a.out".to_string()]);
+        let mut settings = LanguageSettings::new(Some(vec![compile]), Tool::new("./a.out", true, Vec::new()), "c");
+        settings.placeholders = placeholders.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        settings
+    }
+
+    #[test]
+    fn keys_follow_the_expanded_commands() {
+        let sh = settings("{cc}", &[("cc", "sh"), ("flags", "-O2")]);
+        assert_eq!(Cache::key("code", &sh), Cache::key("code", &sh.clone()));
+        assert_ne!(Cache::key("code", &sh), Cache::key("other code", &sh));
+        assert_ne!(Cache::key("code", &sh), Cache::key("code", &settings("{cc}", &[("cc", "cat"), ("flags", "-O2")])));
+        assert_ne!(Cache::key("code", &sh), Cache::key("code", &settings("{cc}", &[("cc", "sh"), ("flags", "-O0")])));
+        // Only what the commands end up being matters
+        assert_eq!(Cache::key("code", &sh), Cache::key("code", &settings("sh", &[("flags", "-O2")])));
+    }
+
+    #[test]
+    fn keys_follow_the_files_of_the_tools() {
+        let dir = TempDir::new("tools").unwrap();
+        let compiler = dir.path().join("cc");
+        let settings = settings("{cc}", &[("cc", &compiler.to_string_lossy()), ("flags", "")]);
+        std::fs::write(&compiler, "#!/bin/sh\n").unwrap();
+        let key = Cache::key("code", &settings);
+        assert_eq!(key, Cache::key("code", &settings));
+        std::fs::write(&compiler, "#!/bin/sh\necho upgraded\n").unwrap();
+        assert_ne!(key, Cache::key("code", &settings));
+    }
+
+    #[test]
+    fn stored_compilations_are_fetched() {
+        let dir = TempDir::new("cache").unwrap();
+        let cache = Cache::new(dir.path().join("cache"));
+        let compilation = dir.path().join("compilation");
+        std::fs::create_dir_all(compilation.join("classes")).unwrap();
+        std::fs::write(compilation.join("a.out"), "binary").unwrap();
+        std::fs::write(compilation.join("classes").join("Main.class"), "class").unwrap();
+
+        let fetched = dir.path().join("fetched");
+        assert!(!cache.fetch("key", &fetched).unwrap());
+        cache.store("key", &compilation).unwrap();
+        // Storing the same compilation again keeps the first one
+        cache.store("key", dir.path()).unwrap();
+        assert!(cache.fetch("key", &fetched).unwrap());
+        assert_eq!(std::fs::read_to_string(fetched.join("a.out")).unwrap(), "binary");
+        assert_eq!(std::fs::read_to_string(fetched.join("classes").join("Main.class")).unwrap(), "class");
+
+        assert_eq!(cache.fetch_output("output").unwrap(), None);
+        cache.store_output("output", b"1 2 3").unwrap();
+        assert_eq!(cache.fetch_output("output").unwrap().as_deref(), Some(&b"1 2 3"[..]));
+
+        cache.clear().unwrap();
+        assert!(!cache.fetch("key", &dir.path().join("again")).unwrap());
+    }
+}
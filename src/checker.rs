@@ -0,0 +1,213 @@
+extern crate serde;
+extern crate regex;
+
+use serde::{Serialize, Deserialize};
+use std::sync::OnceLock;
+use crate::Program;
+
+/// Absolute tolerance used by the float checker when none is given
+const DEFAULT_TOLERANCE: f64 = 1e-6;
+
+/// Describes how the output of a competitor is compared against the expected one
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(tag = "type")]
+pub enum Checker {
+    /// Both outputs must be equal, ignoring leading and trailing whitespace
+    #[default]
+    Exact,
+    /// Both outputs must have the same whitespace separated tokens
+    Tokens,
+    /// Both outputs must have the same lines, ignoring blank ones at the end, each one with the same tokens
+    Lines,
+    /// Like `Tokens`, but numbers match if they are within any of the tolerances
+    Float {
+        #[serde(default)]
+        absolute: Option<f64>,
+        #[serde(default)]
+        relative: Option<f64>,
+        /// Other tokens match ignoring the case of the letters
+        #[serde(default)]
+        case_insensitive: bool,
+        /// The whitespace between tokens has to be the same too
+        #[serde(default)]
+        space_sensitive: bool
+    },
+    /// Like `Exact`, but ignoring the case of the letters
+    CaseInsensitive,
+    /// Like `Tokens`, but ignoring the case of the letters
+    CaseInsensitiveTokens,
+    /// Both outputs must have the same lines, in any order
+    UnorderedLines,
+    /// The whole output, without leading and trailing whitespace, must match the expression
+    Regex {
+        pattern: String,
+        /// The expression compiled the first time it is used
+        #[serde(skip)]
+        compiled: OnceLock<Result<regex::Regex, regex::Error>>
+    },
+    /// A program that receives the input, the output and the expected output files, following the testlib conventions
+    Program(Program)
+}
+
+impl Checker {
+    /// Checks that the checker can be used, returning a description of the problem otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Checker::Float{absolute, relative, ..} => {
+                if absolute.iter().chain(relative.iter()).any(|tolerance| tolerance.is_nan() || *tolerance < 0.0) {
+                    return Err("tolerances have to be non-negative numbers".to_string());
+                }
+                Ok(())
+            },
+            Checker::Regex{pattern, compiled} => Checker::regex(pattern, compiled).map(|_| ()).map_err(|e| format!("{}", e)),
+            _ => Ok(())
+        }
+    }
+
+    /// Indicates if the candidate output is accepted given the expected one. Checker programs are never run here.
+    pub fn check(&self, candidate: &str, expected: &str) -> bool {
+        match self {
+            Checker::Exact => candidate.trim() == expected.trim(),
+            Checker::Tokens => candidate.split_whitespace().eq(expected.split_whitespace()),
+            Checker::Lines => {
+                let mut expected = expected.trim_end().lines();
+                candidate.trim_end().lines().all(|line| expected.next().is_some_and(|other| line.split_whitespace().eq(other.split_whitespace())))
+                    && expected.next().is_none()
+            },
+            Checker::Float{absolute, relative, case_insensitive, space_sensitive} => {
+                let (absolute, relative) = match (*absolute, *relative) {
+                    (None, None) => (Some(DEFAULT_TOLERANCE), None),
+                    other => other
+                };
+                let mut candidate = Checker::spaced_tokens(candidate);
+                let mut expected = Checker::spaced_tokens(expected);
+                loop {
+                    match (candidate.next(), expected.next()) {
+                        (Some((c, c_space)), Some((e, e_space))) => {
+                            if *space_sensitive && c_space != e_space {
+                                return false;
+                            }
+                            let matches = match (c.parse::<f64>(), e.parse::<f64>()) {
+                                (Ok(c), Ok(e)) => {
+                                    let difference = (c - e).abs();
+                                    c == e
+                                        || absolute.map(|tolerance| difference <= tolerance).unwrap_or(false)
+                                        || relative.map(|tolerance| difference <= tolerance * e.abs()).unwrap_or(false)
+                                },
+                                _ if *case_insensitive => c.to_lowercase() == e.to_lowercase(),
+                                _ => c == e
+                            };
+                            if !matches {
+                                return false;
+                            }
+                        },
+                        (None, None) => return true,
+                        _ => return false
+                    }
+                }
+            },
+            Checker::CaseInsensitive => candidate.trim().to_lowercase() == expected.trim().to_lowercase(),
+            Checker::CaseInsensitiveTokens => candidate.split_whitespace().map(str::to_lowercase).eq(expected.split_whitespace().map(str::to_lowercase)),
+            Checker::UnorderedLines => {
+                let lines = |text: &str| {
+                    let mut lines: Vec<String> = text.trim().lines().map(|line| line.trim_end().to_string()).collect();
+                    lines.sort();
+                    lines
+                };
+                lines(candidate) == lines(expected)
+            },
+            Checker::Regex{pattern, compiled} => match Checker::regex(pattern, compiled) {
+                Ok(regex) => regex.is_match(candidate.trim()),
+                Err(e) => {
+                    log::error!("Invalid checker expression, {}", e);
+                    false
+                }
+            },
+            Checker::Program(program) => {
+                log::error!("Checker \"{}\" has to be run as a program", program.source);
+                false
+            }
+        }
+    }
+
+    /// Whitespace separated tokens of the text without leading and trailing whitespace, each with the whitespace that
+    /// comes before it
+    fn spaced_tokens(text: &str) -> impl Iterator<Item = (&str, &str)> {
+        let mut rest = text.trim();
+        std::iter::from_fn(move || {
+            let start = rest.find(|c: char| !c.is_whitespace())?;
+            let end = rest[start..].find(char::is_whitespace).map(|end| start + end).unwrap_or(rest.len());
+            let item = (&rest[start..end], &rest[..start]);
+            rest = &rest[end..];
+            Some(item)
+        })
+    }
+
+    fn regex<'a>(pattern: &str, compiled: &'a OnceLock<Result<regex::Regex, regex::Error>>) -> Result<&'a regex::Regex, &'a regex::Error> {
+        compiled.get_or_init(|| regex::Regex::new(&format!("^(?:{})$", pattern))).as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_tolerances() {
+        let default = Checker::Float{absolute: None, relative: None, case_insensitive: false, space_sensitive: false};
+        assert!(default.check("0.3333335 2", "0.333333 2"));
+        assert!(!default.check("0.33334 2", "0.333333 2"));
+        assert!(!default.check("0.333333", "0.333333 2"));
+
+        let absolute = Checker::Float{absolute: Some(0.1), relative: None, case_insensitive: false, space_sensitive: false};
+        assert!(absolute.check("1000.05", "1000"));
+        assert!(!absolute.check("1000.5", "1000"));
+
+        let relative = Checker::Float{absolute: None, relative: Some(0.001), case_insensitive: false, space_sensitive: false};
+        assert!(relative.check("1000.5", "1000"));
+        assert!(!relative.check("0.0015", "0.001"));
+
+        // A number within any of the tolerances matches, other tokens have to be equal
+        let both = Checker::Float{absolute: Some(0.01), relative: Some(0.001), case_insensitive: false, space_sensitive: false};
+        assert!(both.check("1000.5\n0.005 yes", "1000 0.0 yes"));
+        assert!(!both.check("1000 0 no", "1000 0 yes"));
+        assert!(!both.check("nan", "1"));
+    }
+
+    #[test]
+    fn float_case_and_spacing() {
+        let float = |case_insensitive, space_sensitive| Checker::Float{absolute: Some(1e-6), relative: None, case_insensitive, space_sensitive};
+        for (case_insensitive, space_sensitive) in [(false, false), (true, false), (false, true), (true, true)] {
+            let checker = float(case_insensitive, space_sensitive);
+            assert!(checker.check("0.5 Impossible\n", "0.5000001 Impossible"));
+            assert_eq!(checker.check("0.5 IMPOSSIBLE", "0.5 Impossible"), case_insensitive);
+            assert_eq!(checker.check("0.5  Impossible", "0.5 Impossible"), !space_sensitive);
+            assert_eq!(checker.check("0.5\nimpossible", "0.5 Impossible"), case_insensitive && !space_sensitive);
+            assert!(!checker.check("0.5", "0.5 Impossible"));
+        }
+    }
+
+    #[test]
+    fn token_comparisons() {
+        assert!(Checker::Exact.check("a b\n", "  a b"));
+        assert!(!Checker::Exact.check("a  b", "a b"));
+        assert!(Checker::Tokens.check("a  b\n\nc", "a b c"));
+        assert!(!Checker::Tokens.check("a b", "a b c"));
+        assert!(Checker::Lines.check("a  b\nc \n\n", "a b\nc"));
+        assert!(!Checker::Lines.check("a\nb c", "a b\nc"));
+        assert!(!Checker::Lines.check("\na b", "a b"));
+        assert!(Checker::CaseInsensitive.check("Yes", "YES"));
+        assert!(Checker::CaseInsensitiveTokens.check("Yes\n no", "YES NO"));
+        assert!(Checker::UnorderedLines.check("b\na  \n", "a\nb"));
+        assert!(!Checker::UnorderedLines.check("a\na\nb", "a\nb\nb"));
+    }
+
+    #[test]
+    fn regex_matches_whole_output() {
+        let checker = Checker::Regex{pattern: r"\d+ (yes|no)".to_string(), compiled: OnceLock::new()};
+        assert!(checker.validate().is_ok());
+        assert!(checker.check("42 yes\n", ""));
+        assert!(!checker.check("42 yes please", ""));
+        assert!(Checker::Regex{pattern: "(".to_string(), compiled: OnceLock::new()}.validate().is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Course directories, where every problem is a directory with its solution and tests, instead of a settings file
+extern crate regex;
+extern crate serde;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::Deserialize;
+use crate::{Settings, Proposal, Scenario, Checker, Clock, Language};
+use crate::{kattis, polygon};
+
+/// File that marks a directory of the course as a problem, with its points and limits
+const PROBLEM_FILE: &str = "problem.toml";
+/// Directory of a problem with its tests
+const TESTS_DIR: &str = "tests";
+/// Name, without extension, of the reference solution of a problem
+const SOLUTION_NAME: &str = "solution";
+/// Maximum time of the tests, in seconds, when the problem does not set one
+const DEFAULT_MAX_TIME: f32 = 1.0;
+/// Runs of digits and of other characters in a name, compiled once
+static CHUNKS: OnceLock<Regex> = OnceLock::new();
+
+/// Contents of `problem.toml`, where every value is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ProblemFile {
+    /// Points of every test, 1 by default
+    points: Option<u32>,
+    max_time: Option<f32>,
+    max_ram: Option<u32>,
+    max_output: Option<u64>,
+    clock: Clock,
+    /// Language of the solution, guessed from its extension by default
+    language: Option<Language>,
+    /// Filename of the files of the competitors, the name of the directory by default
+    filename: Option<String>,
+    checker: Option<Checker>,
+    /// Values for single tests, by the name of their files
+    tests: HashMap<String, TestFile>
+}
+
+/// Values of `problem.toml` for a single test, over the ones of the problem
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TestFile {
+    points: Option<u32>,
+    max_time: Option<f32>,
+    max_ram: Option<u32>
+}
+
+/// Reads the settings of a course directory. Every directory in it with a `problem.toml` file is a problem, named
+/// after the directory, and so is every Kattis or Polygon package in it, or the directory itself if it is one.
+/// Problems have a `solution.<extension>` and a test for every `tests/<name>.in` file, sorted by name. The arguments
+/// of a test are the lines of `tests/<name>.args` and its expected output is `tests/<name>.out`, or the output of the
+/// solution when missing.
+pub fn load(dir: &Path) -> io::Result<Settings> {
+    // Paths are absolute, so they do not depend on where the arena is built from
+    let dir = dir.canonicalize()?;
+    // A single Kattis or Polygon package is a course of its own
+    let problems = if dir.join(kattis::PACKAGE_FILE).is_file() || polygon::package_file(&dir).is_some() {
+        vec![dir.clone()]
+    } else {
+        std::fs::read_dir(&dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<_>>()?
+    };
+
+    let mut proposals = HashMap::new();
+    for path in problems {
+        let proposal = if path.join(PROBLEM_FILE).is_file() {
+            problem(&name_of(&path), &path)
+        } else if path.join(kattis::PACKAGE_FILE).is_file() {
+            kattis::proposal(&name_of(&path), &path)
+        } else if polygon::package_file(&path).is_some() {
+            polygon::proposal(&name_of(&path), &path)
+        } else {
+            continue;
+        };
+        let proposal = proposal.map_err(|e| io::Error::new(e.kind(), format!("problem \"{}\", {}", name_of(&path), e)))?;
+        proposals.insert(name_of(&path), proposal);
+    }
+    if proposals.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no directory of {} is a problem or a package", dir.display())));
+    }
+    Ok(Settings{proposals, language_settings: None})
+}
+
+/// Name of a problem, which is the one of its directory
+fn name_of(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Reads the proposal of the directory of a problem
+fn problem(name: &str, dir: &Path) -> io::Result<Proposal> {
+    let settings: ProblemFile = toml::from_str(&std::fs::read_to_string(dir.join(PROBLEM_FILE))?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid, {}", PROBLEM_FILE, e)))?;
+
+    let solution = solution(dir)?;
+    let language = match settings.language.clone() {
+        Some(language) => language,
+        None => {
+            let extension = solution.extension().map(|extension| extension.to_string_lossy()).unwrap_or_default();
+            language_of(&extension).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("no language has the extension \"{}\", set one in {}", extension, PROBLEM_FILE))
+            })?
+        }
+    };
+
+    // Checker programs are found next to the problem, like its solution
+    let checker = settings.checker.clone().map(|checker| match checker {
+        Checker::Program(mut program) => {
+            program.source = dir.join(&program.source).to_string_lossy().into_owned();
+            Checker::Program(program)
+        },
+        checker => checker
+    });
+
+    let tests = tests(dir)?;
+    if let Some(test) = settings.tests.keys().find(|test| !tests.contains(test)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has values for \"{}\", which is not a test", PROBLEM_FILE, test)));
+    }
+    let scenarios: Vec<Scenario> = tests.iter().map(|test| scenario(dir, test, &settings)).collect::<io::Result<_>>()?;
+    if scenarios.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("there are no .in files in {}", TESTS_DIR)));
+    }
+
+    Ok(Proposal {
+        points: scenarios.iter().map(|scenario| scenario.points).sum(),
+        scenarios,
+        solution: solution.to_string_lossy().into_owned(),
+        filename: Some(settings.filename.unwrap_or_else(|| name.to_string())),
+        language,
+        checker,
+        interactor: None,
+        clock: settings.clock,
+        fixtures: Vec::new(),
+        generators: HashMap::new()
+    })
+}
+
+/// Finds the only `solution.<extension>` file of a problem
+fn solution(dir: &Path) -> io::Result<PathBuf> {
+    let mut solutions = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.file_stem().map(|stem| stem == SOLUTION_NAME).unwrap_or(false) && path.extension().is_some() {
+            solutions.push(path);
+        }
+    }
+    match solutions.len() {
+        0 => Err(io::Error::new(io::ErrorKind::NotFound, format!("there is no {} file", SOLUTION_NAME))),
+        1 => Ok(solutions.remove(0)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("there is more than one {} file", SOLUTION_NAME)))
+    }
+}
+
+/// Names of the tests of a problem, in natural order, so that `2` comes before `10`
+fn tests(dir: &Path) -> io::Result<Vec<String>> {
+    let mut tests = Vec::new();
+    for entry in std::fs::read_dir(dir.join(TESTS_DIR))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map(|extension| extension == "in").unwrap_or(false) {
+            if let Some(stem) = path.file_stem() {
+                tests.push(stem.to_string_lossy().into_owned());
+            }
+        }
+    }
+    tests.sort_by_cached_key(|test| natural_key(test));
+    Ok(tests)
+}
+
+/// Key that sorts names with the numbers in them compared by their value
+pub(crate) fn natural_key(name: &str) -> Vec<(Option<u128>, String)> {
+    let chunks = CHUNKS.get_or_init(|| Regex::new(r"\d+|\D+").unwrap());
+    chunks.find_iter(name).map(|chunk| (chunk.as_str().parse::<u128>().ok(), chunk.as_str().to_string())).collect()
+}
+
+/// Describes a test of a problem as a scenario
+fn scenario(dir: &Path, test: &str, settings: &ProblemFile) -> io::Result<Scenario> {
+    let tests = dir.join(TESTS_DIR);
+    let arguments = match std::fs::read_to_string(tests.join(format!("{}.args", test))) {
+        Ok(content) => Some(content.lines().map(String::from).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e)
+    };
+    let output = tests.join(format!("{}.out", test));
+    let values = settings.tests.get(test);
+    Ok(Scenario {
+        arguments,
+        env: HashMap::new(),
+        fixtures: Vec::new(),
+        input: None,
+        generator: None,
+        input_file: Some(tests.join(format!("{}.in", test)).to_string_lossy().into_owned()),
+        output: None,
+        output_file: if output.is_file() { Some(output.to_string_lossy().into_owned()) } else { None },
+        checker: None,
+        max_time: values.and_then(|values| values.max_time).or(settings.max_time).unwrap_or(DEFAULT_MAX_TIME),
+        max_ram: values.and_then(|values| values.max_ram).or(settings.max_ram),
+        max_output: settings.max_output,
+        peak_ram: None,
+        points: values.and_then(|values| values.points).or(settings.points).unwrap_or(1)
+    })
+}
+
+/// Preset whose sources have the extension. Python files are taken as Python3, the newest of the presets sharing it.
+pub(crate) fn language_of(extension: &str) -> Option<Language> {
+    Language::PRESETS.iter().rev().map(|name| Language::new(*name)).find(|language| {
+        language.default_settings().map(|settings| settings.extensions().any(|known| known == extension)).unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_ordering() {
+        let mut names = vec!["test10", "test2", "test1b", "test1a", "sample", "test02", "test1"];
+        names.sort_by_key(|name| natural_key(name));
+        assert_eq!(names, vec!["sample", "test1", "test1a", "test1b", "test02", "test2", "test10"]);
+    }
+
+    #[test]
+    fn courses_are_read_from_their_directories() {
+        let course = tempdir::TempDir::new("course").unwrap();
+        let sum = course.path().join("sum");
+        std::fs::create_dir_all(sum.join(TESTS_DIR)).unwrap();
+        std::fs::write(sum.join("solution.py"), "print(sum(map(int, input().split())))\n").unwrap();
+        std::fs::write(sum.join(PROBLEM_FILE), "points = 2\nmax_time = 3.0\nmax_ram = 64\n\n[tests.10]\npoints = 5\nmax_time = 0.5\n").unwrap();
+        for test in ["1", "2", "10"] {
+            std::fs::write(sum.join(TESTS_DIR).join(format!("{}.in", test)), "1 2\n").unwrap();
+        }
+        std::fs::write(sum.join(TESTS_DIR).join("1.out"), "3\n").unwrap();
+        std::fs::write(sum.join(TESTS_DIR).join("2.args"), "-v\n--fast\n").unwrap();
+        // Directories without a problem file are not problems
+        std::fs::create_dir(course.path().join("notes")).unwrap();
+
+        let settings = load(course.path()).unwrap();
+        assert_eq!(settings.proposals.len(), 1);
+        let proposal = &settings.proposals["sum"];
+        assert!(proposal.language == Language::new("Python3"));
+        assert_eq!(proposal.filename.as_deref(), Some("sum"));
+        assert!(Path::new(&proposal.solution).is_absolute() && proposal.solution.ends_with("solution.py"));
+
+        let inputs: Vec<_> = proposal.scenarios.iter().map(|scenario| scenario.input_file.clone().unwrap()).collect();
+        assert!(inputs[0].ends_with("/1.in") && inputs[1].ends_with("/2.in") && inputs[2].ends_with("/10.in"));
+        assert!(proposal.scenarios[0].output_file.as_deref().unwrap().ends_with("/1.out"));
+        assert!(proposal.scenarios[1].output_file.is_none());
+        assert_eq!(proposal.scenarios[0].arguments, None);
+        assert_eq!(proposal.scenarios[1].arguments, Some(vec!["-v".to_string(), "--fast".to_string()]));
+
+        let points: Vec<_> = proposal.scenarios.iter().map(|scenario| scenario.points).collect();
+        assert_eq!(points, vec![2, 2, 5]);
+        assert_eq!(proposal.points, 9);
+        let times: Vec<_> = proposal.scenarios.iter().map(|scenario| scenario.max_time).collect();
+        assert_eq!(times, vec![3.0, 3.0, 0.5]);
+        assert!(proposal.scenarios.iter().all(|scenario| scenario.max_ram == Some(64)));
+    }
+
+    #[test]
+    fn broken_courses_are_rejected() {
+        let course = tempdir::TempDir::new("course").unwrap();
+        assert!(load(course.path()).is_err());
+
+        let problem = course.path().join("problem");
+        std::fs::create_dir_all(problem.join(TESTS_DIR)).unwrap();
+        std::fs::write(problem.join(PROBLEM_FILE), "").unwrap();
+        std::fs::write(problem.join(TESTS_DIR).join("1.in"), "").unwrap();
+        // There is no solution yet
+        assert!(load(course.path()).is_err());
+        std::fs::write(problem.join("solution.unknown"), "").unwrap();
+        assert!(load(course.path()).err().unwrap().to_string().contains("no language"));
+        std::fs::rename(problem.join("solution.unknown"), problem.join("solution.sh")).unwrap();
+        assert!(load(course.path()).unwrap().proposals["problem"].language == Language::new("Shell"));
+
+        std::fs::write(problem.join(PROBLEM_FILE), "[tests.2]\npoints = 3\n").unwrap();
+        assert!(load(course.path()).err().unwrap().to_string().contains("not a test"));
+        std::fs::write(problem.join(PROBLEM_FILE), "colour = \"red\"\n").unwrap();
+        assert!(load(course.path()).is_err());
+    }
+}
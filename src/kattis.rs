@@ -0,0 +1,327 @@
+//! Problems in the Kattis problem package format, read as proposals
+extern crate log;
+extern crate serde;
+extern crate serde_yaml;
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::course::{language_of, natural_key};
+use crate::{Proposal, Scenario, Checker, Clock, Language, Program, Protocol};
+
+/// File that marks a directory as a Kattis package
+pub(crate) const PACKAGE_FILE: &str = "problem.yaml";
+/// Directories of the test data, in the order they are graded
+const DATA_DIRS: [&str; 2] = ["data/sample", "data/secret"];
+/// Directories of the output validators, in the legacy and the current versions of the format
+const VALIDATOR_DIRS: [&str; 2] = ["output_validators", "output_validator"];
+/// Directory of the submissions that have to be accepted
+const ACCEPTED_DIR: &str = "submissions/accepted";
+/// File with the settings of the tests in its directory and below, like the arguments of the output validator
+const TEST_DATA_FILE: &str = "testdata.yaml";
+/// File where problemtools leaves the time limit, in the legacy version of the format
+const TIME_LIMIT_FILE: &str = ".timelimit";
+/// Time limit, in seconds, when the package has none
+const DEFAULT_TIME_LIMIT: f32 = 1.0;
+/// Limits of the format when the package does not set them, in MiB
+const DEFAULT_MEMORY: u64 = 2048;
+const DEFAULT_OUTPUT: u64 = 8;
+
+/// Contents of `problem.yaml` that are used, the rest is ignored
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PackageFile {
+    /// `default`, `custom` or `custom interactive`, in the legacy version of the format
+    validation: Option<String>,
+    /// Problem type, like `pass-fail` or `interactive`, in the current version of the format
+    #[serde(rename = "type")]
+    kind: Option<serde_yaml::Value>,
+    validator_flags: Option<String>,
+    limits: Limits
+}
+
+/// Contents of `testdata.yaml` that are used, the rest is ignored
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TestDataFile {
+    /// Arguments of the output validator, a string or a list in the current version of the format
+    output_validator_args: Option<serde_yaml::Value>,
+    /// Arguments of the output validator, in the legacy version of the format
+    output_validator_flags: Option<String>
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Limits {
+    /// Seconds, only in the current version of the format
+    time_limit: Option<f32>,
+    /// MiB
+    memory: Option<u64>,
+    /// MiB
+    output: Option<u64>
+}
+
+/// Reads the proposal of a Kattis package. Every test of `data/sample` and `data/secret` is a scenario worth a point,
+/// and an accepted submission, a compiled one if there is any, is the solution.
+pub(crate) fn proposal(name: &str, dir: &Path) -> io::Result<Proposal> {
+    let package: PackageFile = serde_yaml::from_str(&std::fs::read_to_string(dir.join(PACKAGE_FILE))?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid, {}", PACKAGE_FILE, e)))?;
+    let flags: Vec<String> = package.validator_flags.iter().flat_map(|flags| flags.split_whitespace()).map(String::from).collect();
+
+    let validation = package.validation.clone().unwrap_or_default();
+    let kind = match &package.kind {
+        Some(serde_yaml::Value::String(kind)) => kind.clone(),
+        Some(serde_yaml::Value::Sequence(kinds)) => kinds.iter().filter_map(|kind| kind.as_str()).collect::<Vec<_>>().join(" "),
+        _ => String::new()
+    };
+    let interactive = validation.contains("interactive") || kind.contains("interactive");
+    // The current version of the format has a custom validator whenever there is an output validator
+    let custom = validation.contains("custom") || VALIDATOR_DIRS.iter().any(|validators| dir.join(validators).is_dir());
+    let validator = if interactive || custom { Some(validator(dir, flags.clone())?) } else { None };
+
+    let max_time = match package.limits.time_limit {
+        Some(time_limit) => time_limit,
+        None => match std::fs::read_to_string(dir.join(TIME_LIMIT_FILE)) {
+            Ok(content) => content.trim().parse().map_err(|_e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{} does not hold a number of seconds", TIME_LIMIT_FILE))
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::warn!("The package of \"{}\" has no time limit, {} s are used", name, DEFAULT_TIME_LIMIT);
+                DEFAULT_TIME_LIMIT
+            },
+            Err(e) => return Err(e)
+        }
+    };
+    let max_ram = u32::try_from(package.limits.memory.unwrap_or(DEFAULT_MEMORY) * 1024 * 1024).unwrap_or(u32::MAX);
+    let max_output = package.limits.output.unwrap_or(DEFAULT_OUTPUT) * 1024 * 1024;
+
+    let mut scenarios = Vec::new();
+    let mut interactor_flags = None;
+    for data in DATA_DIRS.iter().map(|data| dir.join(data)).filter(|data| data.is_dir()) {
+        for input in tests(&data)? {
+            let answer = input.with_extension("ans");
+            if !answer.is_file() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no .ans file", input.display())));
+            }
+            // Tests with arguments of their own get a checker of their own, interactors can only have one set of them
+            let test_flags = test_flags(dir, &input)?;
+            let checker = match &validator {
+                _ if test_flags.is_empty() => None,
+                Some(_) if interactive => {
+                    if interactor_flags.get_or_insert_with(|| test_flags.clone()) != &test_flags {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "interactive packages with different validator arguments per test are not supported"));
+                    }
+                    None
+                },
+                Some(validator) => Some(Checker::Program(Program{arguments: [flags.clone(), test_flags].concat(), ..validator.clone()})),
+                None => Some(default_checker(&[flags.clone(), test_flags].concat())?)
+            };
+            scenarios.push(Scenario {
+                arguments: None,
+                env: HashMap::new(),
+                fixtures: Vec::new(),
+                input: None,
+                generator: None,
+                input_file: Some(input.to_string_lossy().into_owned()),
+                output: None,
+                output_file: Some(answer.to_string_lossy().into_owned()),
+                checker,
+                max_time,
+                max_ram: Some(max_ram),
+                max_output: Some(max_output),
+                peak_ram: None,
+                points: 1
+            });
+        }
+    }
+    if scenarios.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "there are no .in files in data/sample or data/secret"));
+    }
+
+    let (checker, interactor) = match validator {
+        Some(mut validator) if interactive => {
+            validator.arguments.extend(interactor_flags.unwrap_or_default());
+            (None, Some(validator))
+        },
+        Some(validator) => (Some(Checker::Program(validator)), None),
+        None => (Some(default_checker(&flags)?), None)
+    };
+
+    let (solution, language) = solution(dir)?;
+    Ok(Proposal {
+        points: scenarios.len() as u32,
+        scenarios,
+        language,
+        solution: solution.to_string_lossy().into_owned(),
+        filename: Some(name.to_string()),
+        checker,
+        interactor,
+        clock: Clock::Wall,
+        fixtures: Vec::new(),
+        generators: HashMap::new()
+    })
+}
+
+/// Every `.in` file under the directory, at any depth, in natural order
+fn tests(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut tests = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if extension(&path) == "in" {
+                tests.push(path);
+            }
+        }
+    }
+    tests.sort_by_cached_key(|test| natural_key(&test.to_string_lossy()));
+    Ok(tests)
+}
+
+/// Arguments of the output validator for a test, from the closest `testdata.yaml` that sets them, up to `data`
+fn test_flags(dir: &Path, input: &Path) -> io::Result<Vec<String>> {
+    let data = dir.join("data");
+    for group in input.ancestors().skip(1).take_while(|group| group.starts_with(&data)) {
+        let path = group.join(TEST_DATA_FILE);
+        if !path.is_file() {
+            continue;
+        }
+        let test_data: TestDataFile = serde_yaml::from_str(&std::fs::read_to_string(&path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid, {}", path.display(), e)))?;
+        let flags = match (test_data.output_validator_args, test_data.output_validator_flags) {
+            (Some(serde_yaml::Value::String(args)), _) | (None, Some(args)) => args.split_whitespace().map(String::from).collect(),
+            (Some(serde_yaml::Value::Sequence(args)), _) => args.iter().map(|arg| match arg {
+                serde_yaml::Value::String(arg) => Ok(arg.clone()),
+                serde_yaml::Value::Number(arg) => Ok(arg.to_string()),
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has an argument that is not a string", path.display())))
+            }).collect::<io::Result<_>>()?,
+            (Some(_), _) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the output_validator_args of {} are not a string or a list", path.display()))),
+            (None, None) => continue
+        };
+        return Ok(flags);
+    }
+    Ok(Vec::new())
+}
+
+/// Picks an accepted submission of a known language, preferring compiled ones, which are the fastest
+fn solution(dir: &Path) -> io::Result<(PathBuf, Language)> {
+    let mut submissions = Vec::new();
+    for entry in std::fs::read_dir(dir.join(ACCEPTED_DIR))? {
+        let path = entry?.path();
+        if let Some(language) = language_of(&extension(&path)).filter(|_| path.is_file()) {
+            let interpreted = language.default_settings().map(|settings| settings.pre_tools.is_none()).unwrap_or(true);
+            submissions.push((interpreted, natural_key(&path.to_string_lossy()), path, language));
+        }
+    }
+    submissions.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    submissions.into_iter().next().map(|(_, _, path, language)| (path, language)).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("no file of {} is in a known language", ACCEPTED_DIR))
+    })
+}
+
+/// Reads the output validator, which has to be a single source file, or a directory with only one
+fn validator(dir: &Path, flags: Vec<String>) -> io::Result<Program> {
+    let mut programs = Vec::new();
+    if dir.join(VALIDATOR_DIRS[1]).is_dir() {
+        programs.push(dir.join(VALIDATOR_DIRS[1]));
+    } else if dir.join(VALIDATOR_DIRS[0]).is_dir() {
+        for entry in std::fs::read_dir(dir.join(VALIDATOR_DIRS[0]))? {
+            programs.push(entry?.path());
+        }
+    }
+    let program = match programs.len() {
+        0 => return Err(io::Error::new(io::ErrorKind::NotFound, "the package has a custom validation, but no output validator")),
+        1 => programs.remove(0),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "packages with more than one output validator are not supported"))
+    };
+
+    let source = if program.is_dir() {
+        let mut files = std::fs::read_dir(&program)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+        if files.len() != 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has to hold a single source file", program.display())));
+        }
+        files.remove(0)
+    } else {
+        program
+    };
+    let language = language_of(&extension(&source)).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} is not in a known language", source.display()))
+    })?;
+    Ok(Program {
+        source: source.to_string_lossy().into_owned(),
+        language,
+        code: None,
+        protocol: Protocol::Kattis,
+        arguments: flags
+    })
+}
+
+/// Built-in checker that behaves like the default output validator with the flags
+fn default_checker(flags: &[String]) -> io::Result<Checker> {
+    let (mut case_sensitive, mut space_change_sensitive) = (false, false);
+    let (mut absolute, mut relative) = (None, None);
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let mut tolerance = || flags.next().and_then(|value| value.parse::<f64>().ok()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("the validator flag {} needs a number", flag))
+        });
+        match flag.as_str() {
+            "case_sensitive" => case_sensitive = true,
+            "space_change_sensitive" => space_change_sensitive = true,
+            "float_absolute_tolerance" => absolute = Some(tolerance()?),
+            "float_relative_tolerance" => relative = Some(tolerance()?),
+            "float_tolerance" => {
+                let value = tolerance()?;
+                absolute = Some(value);
+                relative = Some(value);
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown validator flag \"{}\"", flag)))
+        }
+    }
+
+    Ok(match (absolute.is_some() || relative.is_some(), space_change_sensitive, case_sensitive) {
+        (true, _, _) => Checker::Float{absolute, relative, case_insensitive: !case_sensitive, space_sensitive: space_change_sensitive},
+        (false, true, true) => Checker::Exact,
+        (false, true, false) => Checker::CaseInsensitive,
+        (false, false, true) => Checker::Tokens,
+        (false, false, false) => Checker::CaseInsensitiveTokens
+    })
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map(|extension| extension.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(flags: &str) -> Vec<String> {
+        flags.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn default_checker_flags() {
+        assert!(matches!(default_checker(&[]).unwrap(), Checker::CaseInsensitiveTokens));
+        assert!(matches!(default_checker(&flags("case_sensitive")).unwrap(), Checker::Tokens));
+        assert!(matches!(default_checker(&flags("space_change_sensitive")).unwrap(), Checker::CaseInsensitive));
+        assert!(matches!(default_checker(&flags("space_change_sensitive case_sensitive")).unwrap(), Checker::Exact));
+        assert!(matches!(default_checker(&flags("float_tolerance 1e-4")).unwrap(), Checker::Float{absolute: Some(a), relative: Some(r), case_insensitive: true, space_sensitive: false} if a == 1e-4 && r == 1e-4));
+        assert!(matches!(default_checker(&flags("float_relative_tolerance 0.5 case_sensitive")).unwrap(), Checker::Float{absolute: None, relative: Some(r), case_insensitive: false, space_sensitive: false} if r == 0.5));
+        assert!(matches!(default_checker(&flags("float_tolerance 1 space_change_sensitive")).unwrap(), Checker::Float{case_insensitive: true, space_sensitive: true, ..}));
+        assert!(matches!(default_checker(&flags("case_sensitive float_tolerance 1 space_change_sensitive")).unwrap(), Checker::Float{case_insensitive: false, space_sensitive: true, ..}));
+        assert!(matches!(default_checker(&flags("float_absolute_tolerance 2")).unwrap(), Checker::Float{absolute: Some(a), relative: None, ..} if a == 2.0));
+        let checker = default_checker(&flags("float_tolerance 1e-6")).unwrap();
+        assert!(checker.check("IMPOSSIBLE", "Impossible"));
+    }
+
+    #[test]
+    fn default_checker_rejects_bad_flags() {
+        assert!(default_checker(&flags("float_tolerance")).is_err());
+        assert!(default_checker(&flags("float_tolerance small")).is_err());
+        assert!(default_checker(&flags("ignore_everything")).is_err());
+    }
+}
@@ -1,6 +1,11 @@
 extern crate serde;
 
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::seccomp;
+
+/// Placeholders that are always available in tools
+const BUILT_IN_PLACEHOLDERS: [&str; 2] = ["filename", "pwd"];
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Tool {
@@ -9,85 +14,92 @@ pub struct Tool {
     /// Indicates if the tool is temporal and not system-wide available (for example resulting binaries)
     pub temporal: bool,
     /// Arguments that the utility receives
-    pub arguments: Vec<String>
+    pub arguments: Vec<String>,
+    /// Maximum time, in seconds, a pre tool can take, 60 seconds if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_time: Option<f32>,
+    /// Maximum allowed Ram of a pre tool, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ram: Option<u64>
 }
 
-/// Languages available
-#[derive(Serialize, Deserialize, PartialEq, Hash, Clone)]
-pub enum Language {
-    Rust,
-    Shell,
-    Cpp,
-    C,
-    Python2,
-    Python3
+impl Tool {
+    pub fn new<T: Into<String>>(utility: T, temporal: bool, arguments: Vec<String>) -> Tool {
+        Tool {
+            utility: utility.into(),
+            temporal,
+            arguments,
+            max_time: None,
+            max_ram: None
+        }
+    }
 }
 
+/// Name of a language, either a built-in preset or one declared in the settings
+#[derive(Serialize, Deserialize, PartialEq, Hash, Clone)]
+pub struct Language(String);
+
 impl Eq for Language{}
 
 impl Language {
-    pub fn default_settings(&self) -> LanguageSettings {
-        match self {
-            Language::Rust => {
-                let pre_tools = Some(vec![Tool{utility: "rustc".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "rs".into()
-                }
+    /// Names of the languages that come with default settings
+    pub const PRESETS: [&'static str; 6] = ["Rust", "Shell", "Cpp", "C", "Python2", "Python3"];
+
+    pub fn new<T: Into<String>>(name: T) -> Language {
+        Language(name.into())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the built-in settings of the language, if it is one of the presets
+    pub fn default_settings(&self) -> Option<LanguageSettings> {
+        let settings = match self.name() {
+            "Rust" => {
+                let pre_tools = Some(vec![Tool::new("rustc", false, vec!["-o".into(), "a.exe".into(), "{filename}".into()])]);
+                let tool = Tool::new("{pwd}/a.exe", true, vec![]);
+                LanguageSettings::new(pre_tools, tool, "rs")
             },
-            Language::Shell => {
-                let tool = Tool{utility: "bash".into(), temporal: false, arguments: vec!["{filename}".into()]};
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "sh".into()
-                }
+            "Shell" => {
+                let tool = Tool::new("bash", false, vec!["{filename}".into()]);
+                LanguageSettings::new(None, tool, "sh")
             },
-            Language::Cpp => {
-                let pre_tools = Some(vec![Tool{utility: "g++".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "cpp".into()
-                }
+            "Cpp" => {
+                let pre_tools = Some(vec![Tool::new("g++", false, vec!["-o".into(), "a.exe".into(), "{filename}".into()])]);
+                let tool = Tool::new("{pwd}/a.exe", true, vec![]);
+                LanguageSettings::new(pre_tools, tool, "cpp")
             },
-            Language::C => {
-                let pre_tools = Some(vec![Tool{utility: "gcc".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "c".into()
-                }
+            "C" => {
+                let pre_tools = Some(vec![Tool::new("gcc", false, vec!["-o".into(), "a.exe".into(), "{filename}".into()])]);
+                let tool = Tool::new("{pwd}/a.exe", true, vec![]);
+                LanguageSettings::new(pre_tools, tool, "c")
             },
-            Language::Python2 => {
+            "Python2" => {
                 let tool = if cfg!(target_os = "windows") {
-                    Tool{utility: "python".into(), temporal: false, arguments: vec!["{filename}".into()]}
+                    Tool::new("python", false, vec!["{filename}".into()])
                 } else {
-                    Tool{utility: "python2".into(), temporal: false, arguments: vec!["{filename}".into()]}
+                    Tool::new("python2", false, vec!["{filename}".into()])
                 };
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "py".into()
-                }
+                LanguageSettings::new(None, tool, "py")
             },
-            Language::Python3 => {
+            "Python3" => {
                 let tool = if cfg!(target_os = "windows") {
-                    Tool{utility: "python".into(), temporal: false, arguments: vec!["{filename}".into()]}
+                    Tool::new("python", false, vec!["{filename}".into()])
                 } else {
-                    Tool{utility: "python3".into(), temporal: false, arguments: vec!["{filename}".into()]}
+                    Tool::new("python3", false, vec!["{filename}".into()])
                 };
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "py".into()
-                }
-            }
-        }
+                LanguageSettings::new(None, tool, "py")
+            },
+            _ => return None
+        };
+        Some(settings)
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}", self.0)
     }
 }
 
@@ -98,71 +110,158 @@ pub struct LanguageSettings {
     /// Tool to be executed, i.e. the binary or interpreter with given source file
     pub tool: Tool,
     /// Extension of the files
-    pub extension: String
+    pub extension: String,
+    /// Other extensions under which competitor files are also searched for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_extensions: Vec<String>,
+    /// Name, without extension, given to the source file before running the tools (`source` by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_name: Option<String>,
+    /// Values for custom `{placeholders}` used in the tools, besides `{filename}` and `{pwd}`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub placeholders: HashMap<String, String>,
+    /// Seccomp profile applied to the main tool when running the programs of the competitors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seccomp: Option<String>,
+    /// Variables of the environment of the grader that the programs of the competitors keep, only `PATH` by default
+    #[serde(default = "LanguageSettings::default_inherit_env")]
+    pub inherit_env: Vec<String>,
+    /// Variables set for the programs of the competitors, which can use placeholders
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>
 }
 
 impl LanguageSettings {
-    pub fn default(language: Language) -> LanguageSettings {
-        match language {
-            Language::Rust => {
-                let pre_tools = Some(vec![Tool{utility: "rustc".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "rs".into()
-                }
-            },
-            Language::Shell => {
-                let tool = Tool{utility: "bash".into(), temporal: false, arguments: vec!["{filename}".into()]};
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "sh".into()
-                }
-            },
-            Language::Cpp => {
-                let pre_tools = Some(vec![Tool{utility: "g++".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "cpp".into()
-                }
-            },
-            Language::C => {
-                let pre_tools = Some(vec![Tool{utility: "gcc".into(), temporal: false, arguments: vec!["-o".into(), "a.exe".into(), "{filename}".into()]}]);
-                let tool = Tool{utility: "{pwd}/a.exe".into(), temporal: true, arguments: vec![]};
-                LanguageSettings {
-                    pre_tools,
-                    tool,
-                    extension: "c".into()
-                }
-            },
-            Language::Python2 => {
-                let tool = if cfg!(target_os = "windows") {
-                    Tool{utility: "python".into(), temporal: false, arguments: vec!["{filename}".into()]}
-                } else {
-                    Tool{utility: "python2".into(), temporal: false, arguments: vec!["{filename}".into()]}
-                };
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "py".into()
-                }
-            },
-            Language::Python3 => {
-                let tool = if cfg!(target_os = "windows") {
-                    Tool{utility: "python".into(), temporal: false, arguments: vec!["{filename}".into()]}
-                } else {
-                    Tool{utility: "python3".into(), temporal: false, arguments: vec!["{filename}".into()]}
+    pub fn new<T: Into<String>>(pre_tools: Option<Vec<Tool>>, tool: Tool, extension: T) -> LanguageSettings {
+        LanguageSettings {
+            pre_tools,
+            tool,
+            extension: extension.into(),
+            extra_extensions: Vec::new(),
+            source_name: None,
+            placeholders: HashMap::new(),
+            seccomp: None,
+            inherit_env: LanguageSettings::default_inherit_env(),
+            env: HashMap::new()
+        }
+    }
+
+    fn default_inherit_env() -> Vec<String> {
+        vec!["PATH".to_string()]
+    }
+
+    /// All the extensions a source file in this language might have, the main one first
+    pub fn extensions(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.extension).chain(self.extra_extensions.iter())
+    }
+
+    /// Name of the file the source is written to
+    pub fn source_file(&self) -> String {
+        format!("{}.{}", self.source_name.as_deref().unwrap_or("source"), self.extension)
+    }
+
+    /// Replaces the built-in and custom placeholders in the given text
+    pub fn expand(&self, text: &str, filename: &str, pwd: &str) -> String {
+        let mut text = text.replace("{filename}", filename).replace("{pwd}", pwd);
+        for (name, value) in self.placeholders.iter() {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    /// Checks that the settings are usable, returning a description of the first problem found
+    pub fn validate(&self) -> Result<(), String> {
+        if self.extension.is_empty() || self.extra_extensions.iter().any(|extension| extension.is_empty()) {
+            return Err("extensions can not be empty".to_string());
+        }
+        if let Some(name) = &self.source_name {
+            if name.is_empty() || name.contains(['/', '\\']) {
+                return Err(format!("\"{}\" is not a valid source name", name));
+            }
+        }
+        if let Some(profile) = &self.seccomp {
+            if !seccomp::PROFILES.contains(&profile.as_str()) {
+                return Err(format!("unknown seccomp profile \"{}\", available ones are {}", profile, seccomp::PROFILES.join(", ")));
+            }
+        }
+
+        if let Some(name) = self.inherit_env.iter().chain(self.env.keys()).find(|name| name.is_empty() || name.contains(['=', '\0'])) {
+            return Err(format!("\"{}\" is not a valid environment variable", name));
+        }
+
+        for tool in self.pre_tools.iter().flatten() {
+            if tool.max_time.map(|max_time| max_time.is_nan() || max_time <= 0.0).unwrap_or(false) {
+                return Err(format!("the maximum time of \"{}\" has to be a positive number", tool.utility));
+            }
+        }
+
+        let tools = self.pre_tools.iter().flatten().chain(std::iter::once(&self.tool));
+        let texts = tools.flat_map(|tool| std::iter::once(&tool.utility).chain(tool.arguments.iter())).chain(self.env.values());
+        for text in texts {
+            // We look for every {placeholder} in the text
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find('{') {
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => break
                 };
-                LanguageSettings {
-                    pre_tools: None,
-                    tool,
-                    extension: "py".into()
+                let name = &rest[start + 1..end];
+                if !BUILT_IN_PLACEHOLDERS.contains(&name) && !self.placeholders.contains_key(name) {
+                    return Err(format!("unknown placeholder {{{}}} in \"{}\"", name, text));
                 }
+                rest = &rest[end + 1..];
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> LanguageSettings {
+        let compiler = Tool::new("cc", false, vec!["{filename}".into(), "-o".into(), "{output}".into()]);
+        let mut settings = LanguageSettings::new(Some(vec![compiler]), Tool::new("./{output}", true, Vec::new()), "c");
+        settings.placeholders.insert("output".into(), "a.out".into());
+        settings
+    }
+
+    fn problem(change: impl FnOnce(&mut LanguageSettings)) -> String {
+        let mut settings = settings();
+        change(&mut settings);
+        settings.validate().unwrap_err()
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        assert!(settings().validate().is_ok());
+        for name in Language::PRESETS.iter() {
+            assert!(Language::new(*name).default_settings().unwrap().validate().is_ok(), "{}", name);
+        }
+        assert!(Language::new("Cobol").default_settings().is_none());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn invalid_settings_are_described() {
+        assert!(problem(|settings| settings.extension.clear()).contains("extensions"));
+        assert!(problem(|settings| settings.extra_extensions.push(String::new())).contains("extensions"));
+        assert!(problem(|settings| settings.source_name = Some("../main".into())).contains("source name"));
+        assert!(problem(|settings| settings.source_name = Some(String::new())).contains("source name"));
+        assert!(problem(|settings| settings.seccomp = Some("everything".into())).contains("seccomp profile \"everything\""));
+        assert!(problem(|settings| settings.inherit_env.push("A=B".into())).contains("environment variable"));
+        assert!(problem(|settings| { settings.env.insert(String::new(), "x".into()); }).contains("environment variable"));
+        assert!(problem(|settings| settings.pre_tools.as_mut().unwrap()[0].max_time = Some(0.0)).contains("\"cc\""));
+        assert!(problem(|settings| settings.pre_tools.as_mut().unwrap()[0].max_time = Some(f32::NAN)).contains("positive"));
+        assert!(problem(|settings| { settings.placeholders.remove("output"); }).contains("{output}"));
+        assert!(problem(|settings| { settings.env.insert("HOME".into(), "{home}".into()); }).contains("{home}"));
+        assert!(problem(|settings| settings.tool.arguments.push("{pwd}/{flags}".into())).contains("{flags}"));
+    }
+
+    #[test]
+    fn placeholders_are_expanded() {
+        let settings = settings();
+        assert_eq!(settings.expand("{pwd}/{output} {filename} {other}", "main.c", "/tmp"), "/tmp/a.out main.c {other}");
+        assert_eq!(settings.source_file(), "source.c");
+    }
+}
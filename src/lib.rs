@@ -1,13 +1,25 @@
-pub use self::settings::{Settings, Competitor, Evaluation};
+pub use self::settings::{Settings, Competitor, Evaluation, Verdict, ScenarioResult};
 pub use self::language::{Language, LanguageSettings, Tool};
 pub use self::arena::Arena;
 pub use self::logger::SimpleLogger;
-pub use self::problems::{Scenario, Problem, Proposal};
+pub use self::problems::{Scenario, Problem, Proposal, Program, Protocol, Clock, Fixture};
+pub use self::checker::Checker;
+pub use self::cache::Cache;
+pub use self::process::Sandbox;
+pub use self::tournament::{Tournament, Format, Standing, Match, TournamentResult};
 pub use self::tito::{Tito};
 
 mod settings;
 mod arena;
+mod cache;
+mod checker;
+mod course;
+mod kattis;
 mod logger;
 mod language;
+mod polygon;
 mod problems;
+mod process;
+mod seccomp;
+mod tournament;
 mod tito;
\ No newline at end of file
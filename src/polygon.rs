@@ -0,0 +1,541 @@
+//! Problems in the package format of Codeforces Polygon, read as proposals or written from an arena
+extern crate log;
+extern crate quick_xml;
+extern crate regex;
+extern crate serde;
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use crate::{Arena, Problem, Proposal, Scenario, Checker, Clock, Language, Program, Protocol};
+
+/// Files that describe a package, `problem.xml` in the ones downloaded from Polygon
+const PACKAGE_FILES: [&str; 2] = ["problem.xml", "package.xml"];
+/// Testset that is graded
+const TESTSET: &str = "tests";
+/// Patterns of the test files of exported packages
+const INPUT_PATTERN: &str = "tests/%02d";
+const ANSWER_PATTERN: &str = "tests/%02d.a";
+/// Memory limit, in bytes, of exported problems without one
+const DEFAULT_MEMORY_LIMIT: u64 = 256 * 1024 * 1024;
+/// The `%d` specifier of the file patterns, compiled once
+static SPECIFIER: OnceLock<Regex> = OnceLock::new();
+
+/// Parts of `problem.xml` that are used, the rest is ignored
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "problem")]
+struct Package {
+    #[serde(rename = "@short-name", default, skip_serializing_if = "Option::is_none")]
+    short_name: Option<String>,
+    judging: Judging,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    files: Option<Files>,
+    #[serde(default)]
+    assets: Assets
+}
+
+#[derive(Serialize, Deserialize)]
+struct Judging {
+    /// Files the programs read and write, instead of the standard streams, when not empty
+    #[serde(rename = "@input-file", default)]
+    input_file: String,
+    #[serde(rename = "@output-file", default)]
+    output_file: String,
+    #[serde(default)]
+    testset: Vec<Testset>
+}
+
+#[derive(Serialize, Deserialize)]
+struct Testset {
+    #[serde(rename = "@name")]
+    name: String,
+    /// Milliseconds
+    #[serde(rename = "time-limit")]
+    time_limit: u64,
+    /// Bytes
+    #[serde(rename = "memory-limit")]
+    memory_limit: u64,
+    #[serde(rename = "test-count")]
+    test_count: usize,
+    /// Path of the inputs, with the number of the test as in `printf`
+    #[serde(rename = "input-path-pattern")]
+    input_path_pattern: String,
+    #[serde(rename = "answer-path-pattern", default, skip_serializing_if = "Option::is_none")]
+    answer_path_pattern: Option<String>,
+    #[serde(default)]
+    tests: Tests
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Tests {
+    #[serde(default)]
+    test: Vec<Test>
+}
+
+#[derive(Serialize, Deserialize)]
+struct Test {
+    #[serde(rename = "@method", default, skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(rename = "@points", default, skip_serializing_if = "Option::is_none")]
+    points: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Files {
+    #[serde(default)]
+    resources: Resources
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Resources {
+    #[serde(default)]
+    file: Vec<Source>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Source {
+    #[serde(rename = "@path")]
+    path: String,
+    #[serde(rename = "@type", default)]
+    kind: String
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Assets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checker: Option<Asset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interactor: Option<Asset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    solutions: Option<Solutions>
+}
+
+/// Checker or interactor, either a standard one of Polygon, known by its name, or a program of the package
+#[derive(Serialize, Deserialize)]
+struct Asset {
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(rename = "@type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<Source>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Solutions {
+    #[serde(default)]
+    solution: Vec<Solution>
+}
+
+#[derive(Serialize, Deserialize)]
+struct Solution {
+    /// `main` for the reference solution, others like `wrong-answer` are ignored
+    #[serde(rename = "@tag")]
+    tag: String,
+    source: Source
+}
+
+/// File that describes the package in the directory, if it is one
+pub(crate) fn package_file(dir: &Path) -> Option<PathBuf> {
+    PACKAGE_FILES.iter().map(|name| dir.join(name)).find(|path| path.is_file())
+}
+
+/// Reads the proposal of a Polygon package, which has to hold its generated tests, like the full packages do. The
+/// checker and interactor are programs of the package, with the resources they include, like `testlib.h`, inlined.
+pub(crate) fn proposal(name: &str, dir: &Path) -> io::Result<Proposal> {
+    let file = package_file(dir).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "there is no problem.xml"))?;
+    let package: Package = quick_xml::de::from_str(&std::fs::read_to_string(&file)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid, {}", file.display(), e)))?;
+    if !package.judging.input_file.is_empty() || !package.judging.output_file.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "programs reading or writing files instead of the standard streams are not supported"));
+    }
+    let testset = package.judging.testset.iter().find(|testset| testset.name == TESTSET)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("there is no \"{}\" testset", TESTSET)))?;
+
+    // Tests without points are worth nothing when the others have them, as in Polygon
+    let weighted = testset.tests.test.iter().any(|test| test.points.is_some());
+    let scale = points_scale(testset.tests.test.iter().filter_map(|test| test.points))?;
+    let mut scenarios = Vec::new();
+    for idx in 1..=testset.test_count {
+        let input = dir.join(test_path(&testset.input_path_pattern, idx));
+        if !input.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is missing, generated tests are only in full packages", input.display())));
+        }
+        let answer = testset.answer_path_pattern.as_ref().map(|pattern| dir.join(test_path(pattern, idx))).filter(|answer| answer.is_file());
+        let points = match testset.tests.test.get(idx - 1).and_then(|test| test.points) {
+            Some(points) => (points * scale).round() as u32,
+            None if weighted => 0,
+            None => 1
+        };
+        scenarios.push(Scenario {
+            arguments: None,
+            env: HashMap::new(),
+            fixtures: Vec::new(),
+            input: None,
+            generator: None,
+            input_file: Some(input.to_string_lossy().into_owned()),
+            output: None,
+            output_file: answer.map(|answer| answer.to_string_lossy().into_owned()),
+            checker: None,
+            max_time: testset.time_limit as f32 / 1000.0,
+            max_ram: Some(u32::try_from(testset.memory_limit).unwrap_or(u32::MAX)),
+            max_output: None,
+            peak_ram: None,
+            points
+        });
+    }
+
+    let resources = resources(dir, package.files.as_ref());
+    let program = |asset: &Asset| -> io::Result<Option<Program>> {
+        let source = match &asset.source {
+            Some(source) => source,
+            None => return Ok(None)
+        };
+        let path = dir.join(&source.path);
+        Ok(Some(Program {
+            language: language_of(&source.kind)?,
+            code: Some(inline_resources(&std::fs::read_to_string(&path)?, &resources)),
+            source: path.to_string_lossy().into_owned(),
+            protocol: Protocol::Testlib,
+            arguments: Vec::new()
+        }))
+    };
+    let checker = match &package.assets.checker {
+        Some(Asset{source: None, name: Some(name), ..}) => Some(standard_checker(name)?),
+        Some(asset) => program(asset)?.map(Checker::Program),
+        None => None
+    };
+    let interactor = match &package.assets.interactor {
+        Some(asset) => program(asset)?,
+        None => None
+    };
+
+    let solution = package.assets.solutions.iter().flat_map(|solutions| solutions.solution.iter()).find(|solution| solution.tag == "main")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "there is no main solution"))?;
+    Ok(Proposal {
+        points: scenarios.iter().map(|scenario| scenario.points).sum(),
+        scenarios,
+        solution: dir.join(&solution.source.path).to_string_lossy().into_owned(),
+        filename: Some(package.short_name.clone().unwrap_or_else(|| name.to_string())),
+        language: language_of(&solution.source.kind)?,
+        checker,
+        interactor,
+        clock: Clock::Wall,
+        fixtures: Vec::new(),
+        generators: HashMap::new()
+    })
+}
+
+/// Built-in checker that compares like a standard checker of Polygon, known by a name like `std::wcmp.cpp`
+fn standard_checker(name: &str) -> io::Result<Checker> {
+    let float = |absolute, relative| Checker::Float{absolute, relative, case_insensitive: false, space_sensitive: false};
+    // Integers are read by testlib only when written without signs or zeros to spare, so they match as tokens
+    Ok(match name.strip_prefix("std::").and_then(|name| name.strip_suffix(".cpp")) {
+        Some("wcmp" | "ncmp" | "icmp" | "hcmp") => Checker::Tokens,
+        Some("lcmp") => Checker::Lines,
+        // Lines are compared as they are, only whitespace at their ends is not significant here
+        Some("fcmp") => Checker::Exact,
+        Some("yesno" | "nyesno") => Checker::CaseInsensitiveTokens,
+        Some("rcmp4") => float(Some(1e-4), Some(1e-4)),
+        Some("rcmp6" | "dcmp") => float(Some(1e-6), Some(1e-6)),
+        Some("rcmp9") => float(Some(1e-9), Some(1e-9)),
+        Some("rcmp" | "acmp") => float(Some(1.5e-6), None),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the standard checker \"{}\" is not supported", name)))
+    })
+}
+
+/// Factor that turns the points of every test into whole numbers, keeping how much each one is worth against the
+/// others. Points with more than three decimals are not supported.
+fn points_scale(points: impl Iterator<Item = f64> + Clone) -> io::Result<f64> {
+    for scale in [1.0, 10.0, 100.0, 1000.0] {
+        if points.clone().all(|points| ((points * scale) - (points * scale).round()).abs() < 1e-6) {
+            return Ok(scale);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "the points of the tests have to be whole numbers, or have at most three decimals"))
+}
+
+/// Writes every problem of the arena as a Polygon package, in a directory named after it, with the reference
+/// solution embedded in the arena as the main solution.
+pub(crate) fn export(arena: &Arena, dir: &Path) -> io::Result<()> {
+    // Arenas could come from anywhere, so names can not lead outside of the directory
+    for name in arena.problems.keys() {
+        let mut components = Path::new(name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("\"{}\" can not be the name of a directory", name)));
+        }
+    }
+    for (name, problem) in arena.problems.iter() {
+        export_problem(name, problem, &dir.join(name)).map_err(|e| io::Error::new(e.kind(), format!("problem \"{}\", {}", name, e)))?;
+    }
+    Ok(())
+}
+
+fn export_problem(name: &str, problem: &Problem, dir: &Path) -> io::Result<()> {
+    if problem.scenarios.iter().any(|scenario| scenario.arguments.is_some() || !scenario.env.is_empty() || !scenario.fixtures.is_empty() || scenario.checker.is_some())
+        || !problem.fixtures.is_empty() {
+        log::warn!("Arguments, environments, fixtures and checkers of single scenarios of \"{}\" can not be exported", name);
+    }
+    // A testset has a single time and memory limit, the largest ones of the scenarios are kept
+    let time_limit = problem.scenarios.iter().map(|scenario| (scenario.max_time * 1000.0).round() as u64).max().unwrap_or_default();
+    let max_ram = problem.scenarios.iter().filter_map(|scenario| scenario.max_ram).max();
+    if problem.scenarios.iter().any(|scenario| (scenario.max_time * 1000.0).round() as u64 != time_limit || scenario.max_ram != max_ram) {
+        log::warn!("The scenarios of \"{}\" have different limits, the largest ones are exported", name);
+    }
+
+    std::fs::create_dir_all(dir.join("tests"))?;
+    std::fs::create_dir_all(dir.join("files"))?;
+    let mut numeric = true;
+    for (idx, scenario) in problem.scenarios.iter().enumerate() {
+        let input = data(&scenario.input, &scenario.input_file)?.unwrap_or_default();
+        std::fs::write(dir.join(test_path(INPUT_PATTERN, idx + 1)), input)?;
+        if let Some(answer) = data(&scenario.output, &scenario.output_file)? {
+            numeric &= String::from_utf8_lossy(&answer).split_whitespace().all(|token| token.parse::<f64>().is_ok());
+            std::fs::write(dir.join(test_path(ANSWER_PATTERN, idx + 1)), answer)?;
+        }
+    }
+
+    // Only checkers that compare exactly the same way can be exported
+    let checker = match problem.checker.clone().unwrap_or_default() {
+        Checker::Tokens => standard("wcmp"),
+        // rcmp reads every token as a number, and accepts it within the same absolute and relative tolerance
+        Checker::Float{absolute: Some(absolute), relative: Some(relative), case_insensitive: false, space_sensitive: false} if absolute == relative && numeric => {
+            match [(1e-4, "rcmp4"), (1e-6, "rcmp6"), (1e-9, "rcmp9")].iter().find(|(known, _)| *known == absolute) {
+                Some((_, checker)) => standard(checker),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Polygon has no checker with a tolerance of {}", absolute)))
+            }
+        },
+        Checker::Program(program) => write_program(&program, dir, "check")?,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "no standard checker of Polygon compares like its checker, it needs a checker program"))
+    };
+    let interactor = problem.interactor.as_ref().map(|interactor| write_program(interactor, dir, "interactor")).transpose()?;
+    let solution = problem.solution.as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the arena does not have its reference solution, it has to be built again"))?;
+    let solution = Solution{tag: "main".to_string(), source: write_source(solution, dir, "solution")?};
+
+    let package = Package {
+        short_name: Some(problem.filename.clone()),
+        judging: Judging {
+            input_file: String::new(),
+            output_file: String::new(),
+            testset: vec![Testset {
+                name: TESTSET.to_string(),
+                time_limit,
+                memory_limit: max_ram.map(u64::from).unwrap_or(DEFAULT_MEMORY_LIMIT),
+                test_count: problem.scenarios.len(),
+                input_path_pattern: INPUT_PATTERN.to_string(),
+                answer_path_pattern: Some(ANSWER_PATTERN.to_string()),
+                tests: Tests {
+                    test: problem.scenarios.iter().map(|scenario| Test{method: Some("manual".to_string()), points: Some(scenario.points as f64)}).collect()
+                }
+            }]
+        },
+        files: None,
+        assets: Assets{checker: Some(checker), interactor, solutions: Some(Solutions{solution: vec![solution]})}
+    };
+    let mut content = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let mut serializer = quick_xml::se::Serializer::new(&mut content);
+    serializer.indent(' ', 2);
+    package.serialize(serializer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}", e)))?;
+    std::fs::write(dir.join(PACKAGE_FILES[0]), content)
+}
+
+/// One of the standard checkers of Polygon
+fn standard(name: &str) -> Asset {
+    Asset{name: Some(format!("std::{}.cpp", name)), kind: Some("testlib".to_string()), source: None}
+}
+
+/// Writes the code of a checker or interactor in the `files` directory of the package
+fn write_program(program: &Program, dir: &Path, name: &str) -> io::Result<Asset> {
+    if program.protocol != Protocol::Testlib {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not follow the conventions of testlib", program.source)));
+    }
+    Ok(Asset{name: None, kind: Some("testlib".to_string()), source: Some(write_source(program, dir, name)?)})
+}
+
+/// Writes the code of a program in the `files` directory of the package, with its source type
+fn write_source(program: &Program, dir: &Path, name: &str) -> io::Result<Source> {
+    let code = program.code.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("the code of {} is not in the arena", program.source)))?;
+    let (extension, kind) = match program.language.name() {
+        "Cpp" => ("cpp", "cpp.g++17"),
+        "C" => ("c", "c.gcc"),
+        "Python2" => ("py", "python.2"),
+        "Python3" => ("py", "python.3"),
+        "Rust" => ("rs", "rust"),
+        other => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Polygon does not know the language {}", other)))
+    };
+    let path = format!("files/{}.{}", name, extension);
+    std::fs::write(dir.join(&path), code)?;
+    Ok(Source{path, kind: kind.to_string()})
+}
+
+/// Test data of a scenario, either inline or in a file
+fn data(inline: &Option<String>, file: &Option<String>) -> io::Result<Option<Vec<u8>>> {
+    match (inline, file) {
+        (Some(text), _) => Ok(Some(text.as_bytes().to_vec())),
+        (None, Some(file)) => std::fs::read(file).map(Some),
+        (None, None) => Ok(None)
+    }
+}
+
+/// Expands the `%d` of a path pattern, with its width and padding, with the number of a test
+fn test_path(pattern: &str, idx: usize) -> String {
+    let specifier = SPECIFIER.get_or_init(|| Regex::new(r"%(0?)(\d*)d").unwrap());
+    specifier.replace(pattern, |captures: &regex::Captures| {
+        let width = captures[2].parse().unwrap_or(0);
+        if &captures[1] == "0" {
+            format!("{:0width$}", idx, width = width)
+        } else {
+            format!("{:width$}", idx, width = width)
+        }
+    }).into_owned()
+}
+
+/// Language of a source type of Polygon, like `cpp.g++17` or `python.3`
+fn language_of(kind: &str) -> io::Result<Language> {
+    let name = match kind.split('.').next().unwrap_or_default() {
+        "cpp" => "Cpp",
+        "c" => "C",
+        "rust" => "Rust",
+        "python" if kind.ends_with('2') => "Python2",
+        "python" => "Python3",
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the source type \"{}\" is not supported", kind)))
+    };
+    Ok(Language::new(name))
+}
+
+/// Contents of the text resources of the package, by their file name
+fn resources(dir: &Path, files: Option<&Files>) -> HashMap<String, String> {
+    let mut resources = HashMap::new();
+    for resource in files.iter().flat_map(|files| files.resources.file.iter()) {
+        let path = dir.join(&resource.path);
+        if let (Some(name), Ok(content)) = (path.file_name(), std::fs::read_to_string(&path)) {
+            resources.insert(name.to_string_lossy().into_owned(), content);
+        }
+    }
+    resources
+}
+
+/// Replaces the `#include "<resource>"` lines with the content of the resource, as programs are a single file
+fn inline_resources(code: &str, resources: &HashMap<String, String>) -> String {
+    code.lines().map(|line| {
+        let included = line.trim().strip_prefix("#include").and_then(|rest| rest.trim().strip_prefix('"')).and_then(|rest| rest.split('"').next());
+        match included.and_then(|name| resources.get(name)) {
+            Some(content) => content.as_str(),
+            None => line
+        }
+    }).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_path_expands_numbers() {
+        assert_eq!(test_path(INPUT_PATTERN, 7), "tests/07");
+        assert_eq!(test_path(ANSWER_PATTERN, 123), "tests/123.a");
+        assert_eq!(test_path("tests/%d.in", 7), "tests/7.in");
+        assert_eq!(test_path("tests/%3d", 7), "tests/  7");
+        assert_eq!(test_path("tests/%03d", 7), "tests/007");
+        assert_eq!(test_path("tests/input", 7), "tests/input");
+    }
+
+    fn problem(scenarios: Vec<Scenario>, checker: Checker) -> Problem {
+        Problem {
+            points: scenarios.iter().map(|scenario| scenario.points).sum(),
+            scenarios,
+            filename: "sum".to_string(),
+            language: Some(Language::new("Cpp")),
+            checker: Some(checker),
+            interactor: None,
+            solution: Some(Program {
+                source: "sum.cpp".to_string(),
+                language: Language::new("Cpp"),
+                code: Some("int main() {}\n".to_string()),
+                protocol: Protocol::Testlib,
+                arguments: Vec::new()
+            }),
+            clock: Clock::Wall,
+            fixtures: Vec::new()
+        }
+    }
+
+    fn export_checker(checker: Checker, output: &str) -> io::Result<Option<String>> {
+        let dir = TempDir::new("polygon").unwrap();
+        export_problem("sum", &problem(vec![scenario("1", output, 1)], checker), dir.path())?;
+        let package: Package = quick_xml::de::from_str(&std::fs::read_to_string(dir.path().join(PACKAGE_FILES[0]))?).unwrap();
+        Ok(package.assets.checker.and_then(|checker| checker.name))
+    }
+
+    fn scenario(input: &str, output: &str, points: u32) -> Scenario {
+        Scenario {
+            arguments: None,
+            env: HashMap::new(),
+            fixtures: Vec::new(),
+            input: Some(input.to_string()),
+            generator: None,
+            input_file: None,
+            output: Some(output.to_string()),
+            output_file: None,
+            checker: None,
+            max_time: 1.0,
+            max_ram: None,
+            max_output: None,
+            peak_ram: None,
+            points
+        }
+    }
+
+    #[test]
+    fn exported_packages_can_be_imported() {
+        let dir = TempDir::new("polygon").unwrap();
+        let mut exported = problem(vec![scenario("1 2\n", "3\n", 2), scenario("2 2\n", "4\n", 3)], Checker::Tokens);
+        exported.scenarios[1].max_ram = Some(64 * 1024 * 1024);
+        export_problem("sum", &exported, dir.path()).unwrap();
+
+        let imported = proposal("other", dir.path()).unwrap();
+        assert_eq!(imported.filename.as_deref(), Some("sum"));
+        assert_eq!(imported.language.name(), "Cpp");
+        assert_eq!(std::fs::read_to_string(&imported.solution).unwrap(), "int main() {}\n");
+        assert!(matches!(imported.checker, Some(Checker::Tokens)));
+        assert_eq!(imported.points, 5);
+        assert_eq!(imported.scenarios.iter().map(|scenario| scenario.points).collect::<Vec<_>>(), vec![2, 3]);
+        for (imported, exported) in imported.scenarios.iter().zip(exported.scenarios.iter()) {
+            assert_eq!(std::fs::read_to_string(imported.input_file.as_ref().unwrap()).unwrap(), *exported.input.as_ref().unwrap());
+            assert_eq!(std::fs::read_to_string(imported.output_file.as_ref().unwrap()).unwrap(), *exported.output.as_ref().unwrap());
+            assert_eq!(imported.max_time, 1.0);
+            assert_eq!(imported.max_ram, Some(64 * 1024 * 1024));
+        }
+
+        exported.solution = None;
+        assert!(export_problem("sum", &exported, dir.path()).is_err());
+    }
+
+    #[test]
+    fn only_equivalent_checkers_are_exported() {
+        let float = |absolute, relative| Checker::Float{absolute, relative, case_insensitive: false, space_sensitive: false};
+        assert_eq!(export_checker(Checker::Tokens, "2").unwrap().as_deref(), Some("std::wcmp.cpp"));
+        assert_eq!(export_checker(float(Some(1e-6), Some(1e-6)), "0.5 2").unwrap().as_deref(), Some("std::rcmp6.cpp"));
+        assert!(export_checker(float(Some(1e-6), None), "0.5").is_err());
+        assert!(export_checker(float(Some(1e-6), Some(1e-6)), "0.5 impossible").is_err());
+        assert!(export_checker(float(Some(1e-5), Some(1e-5)), "0.5").is_err());
+        assert!(export_checker(Checker::Exact, "2").is_err());
+        assert!(export_checker(Checker::Lines, "2").is_err());
+    }
+
+    #[test]
+    fn standard_checkers_are_built_in() {
+        assert!(matches!(standard_checker("std::wcmp.cpp").unwrap(), Checker::Tokens));
+        assert!(matches!(standard_checker("std::lcmp.cpp").unwrap(), Checker::Lines));
+        assert!(matches!(standard_checker("std::yesno.cpp").unwrap(), Checker::CaseInsensitiveTokens));
+        assert!(matches!(standard_checker("std::rcmp6.cpp").unwrap(), Checker::Float{absolute: Some(a), relative: Some(r), ..} if a == 1e-6 && r == 1e-6));
+        assert!(matches!(standard_checker("std::rcmp.cpp").unwrap(), Checker::Float{absolute: Some(a), relative: None, ..} if a == 1.5e-6));
+        assert!(standard_checker("std::uncmp.cpp").is_err());
+        assert!(standard_checker("wcmp").is_err());
+    }
+}
@@ -1,26 +1,104 @@
 extern crate serde;
 
 use serde::{Serialize, Deserialize};
-use crate::{Language};
+use std::collections::{BTreeMap, HashMap};
+use crate::{Language, Checker};
 
 /// Describes what surrounds the execution of the solution of a problem (or the attempt)
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Scenario {
     /// Arguments provided at execution time
     pub arguments: Option<Vec<String>>,
+    /// Environment variables provided at execution time, over the ones of the language. Placeholders are not expanded.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Files given to the program in this scenario, besides the ones of the problem
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<Fixture>,
     /// Std input for the program
     pub input: Option<String>,
+    /// Command line, like `gen 1000 seed=7`, of a generator of the problem whose output becomes the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    /// File the std input is read from instead, relative to the settings file, or to the arena once built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_file: Option<String>,
     /// Expected output
     #[serde(skip_serializing_if = "Option::is_none")]
     pub output: Option<String>,
+    /// File with the expected output, which is then not taken from the solution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+    /// Comparison used for this scenario, instead of the one of the problem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<Checker>,
     /// Maximum allowed time, in seconds
     pub max_time: f32,
     /// Maximum allowed Ram, in bytes
     pub max_ram: Option<u32>,
+    /// Maximum allowed size of the output, in bytes, 64 MiB if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output: Option<u64>,
+    /// Peak Ram used by the reference solution, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_ram: Option<u64>,
     /// Points that this scenario gives
     pub points: u32
 }
 
+/// File or directory copied into the working directory of the program before every execution
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Fixture {
+    /// Path to the file or directory, relative to the settings file
+    pub path: String,
+    /// Content of every file, in base64, by its path inside the working directory. It is embedded in the arena when
+    /// it is built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<BTreeMap<String, String>>
+}
+
+/// Clock the `max_time` of the scenarios of a problem is measured with
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Clock {
+    /// Time elapsed since the program started
+    #[default]
+    Wall,
+    /// User and system cpu time, with the wall time capped at a few times `max_time`, so sleeping programs end
+    Cpu,
+    /// Both times are limited
+    Both
+}
+
+/// Auxiliary program written by the instructor, like a checker or an interactor
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Program {
+    /// Path to the source code
+    pub source: String,
+    /// Language the program is written in
+    pub language: Language,
+    /// Source code, which is embedded in the arena when it is built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Conventions followed by a checker or interactor, the ones of testlib by default
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Arguments given after the ones of the protocol
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<String>
+}
+
+/// How checkers and interactors receive the files of a scenario and tell their verdict
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Protocol {
+    /// Called as `checker <input> <output> <answer>` or `interactor <input> <output>`. Exit code 0 accepts, 1 and 2
+    /// reject, and 7 gives partial credit
+    #[default]
+    Testlib,
+    /// Output validators of the Kattis problem package format, called as `validator <input> <answer> <feedback_dir>`
+    /// with the output in their input, or talking to the program. Exit code 42 accepts and 43 rejects.
+    Kattis
+}
+
 /// Describes a problem, which has multiple scenarios and a certain language
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Problem {
@@ -30,6 +108,21 @@ pub struct Problem {
     pub filename: String,
     /// If this is present, a specific filename is searched for
     pub language: Option<Language>,
+    /// Comparison used for the outputs, exact by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<Checker>,
+    /// Program the solutions talk to in interactive problems
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<Program>,
+    /// Reference solution, embedded so that the problem can be exported, it is never run when grading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solution: Option<Program>,
+    /// Clock the time limits are measured with
+    #[serde(default)]
+    pub clock: Clock,
+    /// Files given to the program in every scenario
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<Fixture>,
     /// Total points that the problem gives
     pub points: u32
 }
@@ -41,8 +134,26 @@ pub struct Proposal {
     pub scenarios: Vec<Scenario>,
     /// Path to the solution of the problem
     pub solution: String,
+    /// Filename, without extension, of the files of the competitors, the one of the solution by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
     /// Language the problem is written in
     pub language: Language,
+    /// Comparison used for the outputs, exact by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<Checker>,
+    /// Program the solution talks to, which makes the problem interactive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<Program>,
+    /// Clock the time limits are measured with, wall time by default
+    #[serde(default)]
+    pub clock: Clock,
+    /// Files given to the program in every scenario
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<Fixture>,
+    /// Programs that write the inputs of scenarios when the arena is built, by the name scenarios call them with
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub generators: HashMap<String, Program>,
     /// Number of points this problem gives
     pub points: u32
 }
\ No newline at end of file
@@ -0,0 +1,908 @@
+//! Platform specific handling of child processes: resource limits, sandboxing, streams and resource usage gathering
+#[cfg(target_os = "linux")]
+extern crate libc;
+#[cfg(not(target_os = "linux"))]
+extern crate wait_timeout;
+
+use std::io::{Read, Write};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Time the streams of a child are still read once it is waited for, before giving up on the processes holding them
+const STREAMS_GRACE: Duration = Duration::from_secs(2);
+/// Time between two measures of the memory of a process group with a memory limit
+pub const MEMORY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// `MOUNT_ATTR_RDONLY` of `mount_setattr`, missing in libc
+#[cfg(target_os = "linux")]
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+/// `struct mount_attr` of `mount_setattr`, missing in libc
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64
+}
+
+/// Isolation applied to the programs of the competitors
+#[derive(Clone)]
+pub struct Sandbox {
+    /// Maximum number of processes a program can have running at once, itself included
+    pub max_processes: Option<u64>
+}
+
+/// Flag raised from any thread, which a process can be waited for along with
+pub struct Alarm {
+    raised: AtomicBool,
+    /// Eventfd written when the flag is raised, so that waiting for a process wakes up at once
+    #[cfg(target_os = "linux")]
+    event: Option<OwnedFd>
+}
+
+impl Alarm {
+    pub fn new() -> Alarm {
+        #[cfg(target_os = "linux")]
+        let event = match unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) } {
+            -1 => None,
+            fd => Some(unsafe { OwnedFd::from_raw_fd(fd) })
+        };
+        Alarm {
+            raised: AtomicBool::new(false),
+            #[cfg(target_os = "linux")]
+            event
+        }
+    }
+
+    pub fn raise(&self) {
+        self.raised.store(true, Ordering::SeqCst);
+        #[cfg(target_os = "linux")]
+        if let Some(event) = &self.event {
+            let one: u64 = 1;
+            unsafe { libc::write(event.as_raw_fd(), &one as *const u64 as *const libc::c_void, std::mem::size_of::<u64>()) };
+        }
+    }
+
+    pub fn is_raised(&self) -> bool {
+        self.raised.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for Alarm {
+    fn default() -> Alarm {
+        Alarm::new()
+    }
+}
+
+/// Resources consumed by a finished process
+pub struct Usage {
+    /// Peak resident memory, in bytes, of the process and its waited-for descendants, or of its whole group when it
+    /// had a memory limit
+    pub peak_ram: Option<u64>,
+    /// User and system cpu time of the process and its waited-for descendants
+    pub cpu_time: Option<Duration>,
+    /// Whether the group was killed for going over its memory limit
+    pub out_of_memory: bool
+}
+
+/// Resident memory a process group can use, enforced while it runs
+#[derive(Clone, Copy)]
+pub struct MemoryLimit {
+    /// Bytes of resident memory the processes of the group can use together
+    pub max_ram: u64,
+    /// Whether the group runs in the sandbox, whose intermediate process and init are not counted
+    pub sandboxed: bool
+}
+
+/// Limits the cpu time of the process spawned by the command, in whole seconds. The process gets `SIGXCPU` once it
+/// is over the limit, and is killed a second later.
+#[cfg(target_os = "linux")]
+pub fn limit_cpu(command: &mut Command, seconds: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit = libc::rlimit {
+        rlim_cur: seconds as libc::rlim_t,
+        rlim_max: seconds.saturating_add(1) as libc::rlim_t
+    };
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn limit_cpu(_command: &mut Command, _seconds: u64) {
+    log::warn!("Cpu time limits are only enforced on linux");
+}
+
+/// Runs the process spawned by the command inside new user, mount, network, pid, ipc and uts namespaces. The whole
+/// filesystem is read-only except for `dir`, there is no network, and killing the spawned process kills every
+/// process the program started.
+///
+/// The spawned process is an intermediate one that exits the same way the program did. Its child is a minimal init
+/// of the new pid namespace, which reaps every process left to it and reports how the program ended. The program is
+/// a child of the init, as the kernel ignores the signals that the init of a namespace sends to itself, like the
+/// `SIGABRT` of `abort`. Linux 5.12 or newer is required, and the process limit only counts the processes of the
+/// sandbox since linux 5.14.
+#[cfg(target_os = "linux")]
+pub fn sandbox(command: &mut Command, sandbox: &Sandbox, dir: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+
+    // Everything is prepared before forking, as allocating in the child of a threaded program is not safe
+    let dir = CString::new(dir.as_os_str().as_bytes())?;
+    let root = CString::new("/")?;
+    let proc_dir = CString::new("/proc")?;
+    let proc_type = CString::new("proc")?;
+    let setgroups = CString::new("/proc/self/setgroups")?;
+    let uid_map = CString::new("/proc/self/uid_map")?;
+    let gid_map = CString::new("/proc/self/gid_map")?;
+    // The program runs as nobody, so it has no capabilities in its namespace to undo the mounts
+    let uid_line = format!("65534 {} 1", unsafe { libc::getuid() });
+    let gid_line = format!("65534 {} 1", unsafe { libc::getgid() });
+    let processes = sandbox.max_processes.map(|max_processes| libc::rlimit {
+        // The intermediate process and the init belong to the sandbox as well
+        rlim_cur: max_processes.saturating_add(2) as libc::rlim_t,
+        rlim_max: max_processes.saturating_add(2) as libc::rlim_t
+    });
+
+    fn check(result: libc::c_int) -> std::io::Result<()> {
+        if result == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    fn write_file(path: &CString, content: &[u8]) -> std::io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY) };
+        check(fd)?;
+        let written = unsafe { libc::write(fd, content.as_ptr() as *const libc::c_void, content.len()) };
+        unsafe { libc::close(fd) };
+        if written != content.len() as isize {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    // The parent waits for the exec of the program through a pipe, so processes that never exec close everything but
+    // the descriptor they keep
+    fn close_descriptors(keep: libc::c_int) {
+        for (first, last) in [(3, keep - 1), (keep + 1, libc::c_int::MAX)] {
+            if first <= last && unsafe { libc::syscall(libc::SYS_close_range, first as libc::c_uint, last as libc::c_uint, 0) } == -1 {
+                for fd in first..last.min(1024) {
+                    unsafe { libc::close(fd) };
+                }
+            }
+        }
+    }
+    fn set_read_only(path: &CString, flags: libc::c_int, read_only: bool) -> std::io::Result<()> {
+        let attr = MountAttr {
+            attr_set: if read_only { MOUNT_ATTR_RDONLY } else { 0 },
+            attr_clr: if read_only { 0 } else { MOUNT_ATTR_RDONLY },
+            propagation: 0,
+            userns_fd: 0
+        };
+        let result = unsafe {
+            libc::syscall(libc::SYS_mount_setattr, libc::AT_FDCWD, path.as_ptr(), flags, &attr as *const MountAttr, std::mem::size_of::<MountAttr>())
+        };
+        check(result as libc::c_int)
+    }
+
+    unsafe {
+        command.pre_exec(move || {
+            check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET | libc::CLONE_NEWPID | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS))?;
+            write_file(&setgroups, b"deny")?;
+            write_file(&uid_map, uid_line.as_bytes())?;
+            write_file(&gid_map, gid_line.as_bytes())?;
+
+            // Mounts stay inside the namespace, and only the working directory is writable
+            check(libc::mount(std::ptr::null(), root.as_ptr(), std::ptr::null(), libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()))?;
+            check(libc::mount(dir.as_ptr(), dir.as_ptr(), std::ptr::null(), libc::MS_BIND | libc::MS_REC, std::ptr::null()))?;
+            set_read_only(&root, libc::AT_RECURSIVE, true)?;
+            set_read_only(&dir, 0, false)?;
+
+            // The init tells the intermediate process the raw wait status of the program through this pipe
+            let mut report = [0; 2];
+            check(libc::pipe2(report.as_mut_ptr(), libc::O_CLOEXEC))?;
+            let init = libc::fork();
+            check(init)?;
+            if init > 0 {
+                libc::close(report[1]);
+                close_descriptors(report[0]);
+                let mut status = 0;
+                while libc::waitpid(init, &mut status, 0) == -1 {
+                    if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                        libc::_exit(127);
+                    }
+                }
+                // Without a report, the init was killed before the program ended
+                let mut reported: libc::c_int = 0;
+                if libc::read(report[0], &mut reported as *mut libc::c_int as *mut libc::c_void, std::mem::size_of::<libc::c_int>()) == std::mem::size_of::<libc::c_int>() as isize {
+                    status = reported;
+                }
+
+                // The intermediate process mirrors how the program ended, without leaving a core dump behind
+                if libc::WIFSIGNALED(status) {
+                    let no_core = libc::rlimit{rlim_cur: 0, rlim_max: 0};
+                    libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+                    libc::signal(libc::WTERMSIG(status), libc::SIG_DFL);
+                    libc::kill(libc::getpid(), libc::WTERMSIG(status));
+                }
+                libc::_exit(libc::WEXITSTATUS(status));
+            }
+
+            // Killing the intermediate process kills the init, and with it the whole namespace
+            libc::close(report[0]);
+            check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+            // A fresh /proc only shows the processes of the sandbox, where the host allows mounting it
+            libc::mount(proc_type.as_ptr(), proc_dir.as_ptr(), proc_type.as_ptr(), libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC, std::ptr::null());
+            if let Some(processes) = &processes {
+                check(libc::setrlimit(libc::RLIMIT_NPROC, processes))?;
+            }
+            // The working directory has to be entered again to be in the writable mount
+            check(libc::chdir(dir.as_ptr()))?;
+
+            let program = libc::fork();
+            check(program)?;
+            if program > 0 {
+                close_descriptors(report[1]);
+                // The init reaps everything left to it until the program ends, which ends the namespace
+                loop {
+                    let mut status = 0;
+                    let waited = libc::waitpid(-1, &mut status, 0);
+                    if waited == program {
+                        libc::write(report[1], &status as *const libc::c_int as *const libc::c_void, std::mem::size_of::<libc::c_int>());
+                        libc::_exit(0);
+                    }
+                    if waited == -1 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                        libc::_exit(127);
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Describes what the sandbox can not enforce in this system, if anything
+#[cfg(target_os = "linux")]
+pub fn sandbox_caveats(sandbox: &Sandbox) -> Option<&'static str> {
+    // Linux never applies the process limit to processes of root, even inside a user namespace
+    if sandbox.max_processes.is_some() && unsafe { libc::getuid() } == 0 {
+        return Some("the process limit is not enforced when grading as root");
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sandbox_caveats(_sandbox: &Sandbox) -> Option<&'static str> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sandbox(_command: &mut Command, _sandbox: &Sandbox, _dir: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "sandboxing is only supported on linux"))
+}
+
+/// Pins the calling thread, and the processes it spawns from then on, to one of the cpus it is allowed to run on,
+/// chosen by `slot`. Returns the chosen cpu.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpu(slot: usize) -> std::io::Result<usize> {
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    let mut allowed: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    if unsafe { libc::sched_getaffinity(0, size, &mut allowed) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let cpus: Vec<usize> = (0..libc::CPU_SETSIZE as usize).filter(|cpu| unsafe { libc::CPU_ISSET(*cpu, &allowed) }).collect();
+    if cpus.is_empty() {
+        return Err(std::io::Error::other("no cpu is available"));
+    }
+
+    let cpu = cpus[slot % cpus.len()];
+    let mut pinned: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(cpu, &mut pinned) };
+    if unsafe { libc::sched_setaffinity(0, size, &pinned) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(cpu)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpu(_slot: usize) -> std::io::Result<usize> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "cpu pinning is only supported on linux"))
+}
+
+/// Signal that terminated the process, if any
+#[cfg(unix)]
+pub fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal()
+}
+
+#[cfg(not(unix))]
+pub fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Starts the process spawned by the command in a process group of its own, so that it can be killed along with
+/// every process it starts. On linux, the process is also killed if the thread that spawned it ends.
+#[cfg(unix)]
+pub fn new_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(0);
+    #[cfg(target_os = "linux")]
+    unsafe {
+        command.pre_exec(|| {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn new_group(_command: &mut Command) {
+}
+
+/// Kills the process group led by the child, or only the child if it does not lead one, and reaps the child
+#[cfg(target_os = "linux")]
+pub fn kill_group(child: &mut Child) -> std::io::Result<()> {
+    if unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) } != 0 {
+        child.kill()?;
+    }
+    child.wait().map(|_| ())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn kill_group(child: &mut Child) -> std::io::Result<()> {
+    child.kill().and_then(|_| child.wait()).map(|_| ())
+}
+
+/// Like [kill_group], without reaping the child, so that the id of the group can not be taken until it is reaped
+#[cfg(target_os = "linux")]
+pub fn kill_group_unreaped(child: &mut Child) -> std::io::Result<()> {
+    if unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) } != 0 {
+        child.kill()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn kill_group_unreaped(child: &mut Child) -> std::io::Result<()> {
+    child.kill()
+}
+
+/// Like [wait_timeout], for a child that leads its own process group. Once the child exits, whatever is left in its
+/// group is killed before reaping the child, so that the id of the group can not be taken by another process
+/// meanwhile. Also indicates if any process was left. Waiting stops early, as on timeout, once `abort` is raised.
+///
+/// With a memory limit, the resident memory of the whole group is measured every `MEMORY_INTERVAL`, and the group is
+/// killed as soon as it goes over the limit. The peak is then the highest of those measures, and of the one of the
+/// child and its waited-for descendants, which catches short peaks.
+#[cfg(target_os = "linux")]
+pub fn wait_group_timeout(child: &mut Child, timeout: Duration, memory: Option<MemoryLimit>, abort: &Alarm) -> std::io::Result<Option<(ExitStatus, Usage, bool)>> {
+    let pid = child.id() as libc::pid_t;
+    let deadline = Instant::now().checked_add(timeout);
+    let mut peak: Option<u64> = None;
+    loop {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())).unwrap_or(Duration::MAX);
+        let interval = if memory.is_some() { remaining.min(MEMORY_INTERVAL) } else { remaining };
+        if wait_exit(pid, interval, Some(abort))? {
+            break;
+        }
+        if abort.is_raised() || remaining <= interval {
+            return Ok(None);
+        }
+        if let Some(memory) = memory {
+            let used = memory_of_group(pid, memory.sandboxed)?;
+            peak = peak.max(Some(used));
+            if used > memory.max_ram {
+                unsafe { libc::killpg(pid, libc::SIGKILL) };
+                let (status, usage) = wait_timeout(child, Duration::MAX)?.ok_or_else(|| std::io::Error::other("the child could not be reaped"))?;
+                return Ok(Some((status, Usage{peak_ram: peak, out_of_memory: true, ..usage}, false)));
+            }
+        }
+    }
+
+    // The child is still a member of its group until it is reaped, so it is not counted
+    let leftovers = group_members(pid).map(|members| members.iter().any(|member| member.pid != pid)).unwrap_or(false);
+    unsafe { libc::killpg(pid, libc::SIGKILL) };
+    // The child already exited, so this only reaps it
+    let (status, mut usage) = wait_timeout(child, Duration::MAX)?.ok_or_else(|| std::io::Error::other("the child could not be reaped"))?;
+    if let Some(memory) = memory {
+        // The intermediate process of the sandbox is a copy of the grader, so its own peak is not the one of the program
+        usage.peak_ram = if memory.sandboxed { peak.or(usage.peak_ram) } else { peak.max(usage.peak_ram) };
+        usage.out_of_memory = usage.peak_ram.map(|peak_ram| peak_ram > memory.max_ram).unwrap_or(false);
+    }
+    Ok(Some((status, usage, leftovers)))
+}
+
+/// Resident memory, in bytes, of the processes in the group led by the child added up. In the sandbox, the
+/// intermediate process that leads the group and its child, the init of the namespace, are left out.
+#[cfg(target_os = "linux")]
+pub fn group_memory(child: &Child, sandboxed: bool) -> std::io::Result<u64> {
+    memory_of_group(child.id() as libc::pid_t, sandboxed)
+}
+
+#[cfg(target_os = "linux")]
+fn memory_of_group(pgid: libc::pid_t, sandboxed: bool) -> std::io::Result<u64> {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+    let members = group_members(pgid)?;
+    let counted = members.iter().filter(|member| !sandboxed || (member.pid != pgid && member.parent != pgid));
+    Ok(counted.map(|member| member.resident * page).sum())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn group_memory(_child: &Child, _sandboxed: bool) -> std::io::Result<u64> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "memory is only measured on linux"))
+}
+
+/// Blocks until the process exits, without reaping it, for at most `timeout` and only while `abort` is not raised.
+/// Indicates if the process exited. The process is waited for through a pidfd, or polled on kernels older than 5.3.
+#[cfg(target_os = "linux")]
+fn wait_exit(pid: libc::pid_t, timeout: Duration, abort: Option<&Alarm>) -> std::io::Result<bool> {
+    let deadline = Instant::now().checked_add(timeout);
+    let pidfd = match unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) } {
+        -1 => None,
+        fd => Some(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+    };
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOHANG | libc::WNOWAIT) } == -1 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+            continue;
+        }
+        if unsafe { info.si_pid() } != 0 {
+            return Ok(true);
+        }
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining == Some(Duration::ZERO) || abort.map(Alarm::is_raised).unwrap_or(false) {
+            return Ok(false);
+        }
+
+        // Negative descriptors are ignored by poll, and a negative time waits forever
+        let event = abort.and_then(|abort| abort.event.as_ref()).map(|event| event.as_raw_fd()).unwrap_or(-1);
+        let mut fds = [
+            libc::pollfd{fd: pidfd.as_ref().map(|pidfd| pidfd.as_raw_fd()).unwrap_or(-1), events: libc::POLLIN, revents: 0},
+            libc::pollfd{fd: event, events: libc::POLLIN, revents: 0}
+        ];
+        let mut wait = remaining.map(|remaining| remaining.as_micros().div_ceil(1000).min(libc::c_int::MAX as u128) as libc::c_int).unwrap_or(-1);
+        if pidfd.is_none() || (abort.is_some() && event == -1) {
+            wait = if wait < 0 { 1 } else { wait.min(1) };
+        }
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, wait) } == -1 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Process in a group, as seen in `/proc`
+#[cfg(target_os = "linux")]
+struct Member {
+    pid: libc::pid_t,
+    parent: libc::pid_t,
+    /// Resident memory, in pages
+    resident: u64
+}
+
+/// Processes in the group, read from `/proc`
+#[cfg(target_os = "linux")]
+fn group_members(pgid: libc::pid_t) -> std::io::Result<Vec<Member>> {
+    let mut members = Vec::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let pid = match entry?.file_name().to_str().and_then(|name| name.parse::<libc::pid_t>().ok()) {
+            Some(pid) => pid,
+            None => continue
+        };
+        // The process might be gone already
+        let stat = match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat,
+            Err(_) => continue
+        };
+        // The name of the program, between parentheses, can hold spaces; the state, parent and group follow it, and
+        // the resident memory is the 24th field
+        let fields: Vec<&str> = match stat.rfind(')') {
+            Some(end) => stat[end + 1..].split_whitespace().collect(),
+            None => continue
+        };
+        let field = |idx: usize| fields.get(idx).and_then(|field| field.parse::<i64>().ok());
+        if let (Some(parent), Some(group), Some(resident)) = (field(1), field(2), field(21)) {
+            if group == pgid as i64 {
+                members.push(Member{pid, parent: parent as libc::pid_t, resident: resident.max(0) as u64});
+            }
+        }
+    }
+    Ok(members)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wait_group_timeout(child: &mut Child, timeout: Duration, memory: Option<MemoryLimit>, _abort: &Alarm) -> std::io::Result<Option<(ExitStatus, Usage, bool)>> {
+    if memory.is_some() {
+        log::warn!("Memory limits are only enforced on linux");
+    }
+    Ok(wait_timeout(child, timeout)?.map(|(status, usage)| (status, usage, false)))
+}
+
+/// Waits for the child to exit for at most `timeout`, returning `None` if it is still running
+#[cfg(target_os = "linux")]
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<(ExitStatus, Usage)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    if !wait_exit(pid, timeout, None)? {
+        return Ok(None);
+    }
+    loop {
+        let mut status = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        match unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } {
+            -1 => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            },
+            _ => {
+                // ru_maxrss is given in kilobytes
+                let cpu_time = |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+                let usage = Usage {
+                    peak_ram: Some(rusage.ru_maxrss as u64 * 1024),
+                    cpu_time: Some(cpu_time(rusage.ru_utime) + cpu_time(rusage.ru_stime)),
+                    out_of_memory: false
+                };
+                return Ok(Some((ExitStatus::from_raw(status), usage)));
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<(ExitStatus, Usage)>> {
+    use wait_timeout::ChildExt;
+
+    Ok(child.wait_timeout(timeout)?.map(|status| (status, Usage{peak_ram: None, cpu_time: None, out_of_memory: false})))
+}
+
+/// Standard output and error gathered from a child
+pub struct Output {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Whether the stdout went over its limit, in which case the rest of it was discarded
+    pub overflow: bool
+}
+
+/// Thread reading a pipe, with what it read so far
+type Drained = (JoinHandle<std::io::Result<()>>, Arc<Mutex<Vec<u8>>>);
+
+/// Feeds the stdin of a child and drains its stdout and stderr in threads of their own, so that the child never
+/// blocks on a full pipe while it is waited for
+pub struct Streams {
+    stdin: Option<JoinHandle<std::io::Result<()>>>,
+    stdout: Option<Drained>,
+    stderr: Option<Drained>,
+    overflow: Arc<Alarm>,
+    /// Receives a message whenever one of the threads ends
+    done: Receiver<()>
+}
+
+impl Streams {
+    /// Starts writing the input to the piped stdin of the child, which is closed afterwards, and reading its piped
+    /// stdout and stderr, keeping at most `max_stdout` and `max_stderr` bytes of them
+    pub fn start(child: &mut Child, input: Option<Vec<u8>>, max_stdout: u64, max_stderr: u64) -> Streams {
+        let overflow = Arc::new(Alarm::new());
+        let (sender, done) = mpsc::channel();
+        let stdin = child.stdin.take().map(|mut pipe| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let result = match input.map(|input| pipe.write_all(&input)) {
+                    // Programs do not need to read their whole input
+                    Some(Err(e)) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(std::io::Error::new(e.kind(), format!("could not feed the input, {}", e))),
+                    _ => Ok(())
+                };
+                let _ = sender.send(());
+                result
+            })
+        });
+        let read = |pipe: Option<Box<dyn Read + Send>>, max: u64, overflow: Arc<Alarm>| pipe.map(|pipe| {
+            let kept = Arc::new(Mutex::new(Vec::new()));
+            let (sender, shared) = (sender.clone(), kept.clone());
+            let handle = std::thread::spawn(move || {
+                let result = drain(pipe, max, &shared, &overflow);
+                let _ = sender.send(());
+                result
+            });
+            (handle, kept)
+        });
+        let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>), max_stdout, overflow.clone());
+        let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>), max_stderr, Arc::new(Alarm::new()));
+        Streams{stdin, stdout, stderr, overflow, done}
+    }
+
+    /// Flag raised as soon as the stdout goes over its limit
+    pub fn overflow(&self) -> &Alarm {
+        &self.overflow
+    }
+
+    /// Waits until every process holding the pipes of the child closes them, and returns what was read. Processes
+    /// that left the group of the child can hold them forever, so they are given up after `STREAMS_GRACE`.
+    pub fn finish(self) -> std::io::Result<Output> {
+        let threads = [self.stdin.is_some(), self.stdout.is_some(), self.stderr.is_some()].iter().filter(|&&thread| thread).count();
+        let deadline = Instant::now() + STREAMS_GRACE;
+        for _ in 0..threads {
+            if self.done.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_err() {
+                log::warn!("A process that outlived the program still holds its streams, they are no longer read");
+                break;
+            }
+        }
+
+        // Threads that are still blocked are left behind, keeping what they read so far
+        fn join(handle: JoinHandle<std::io::Result<()>>) -> std::io::Result<()> {
+            if !handle.is_finished() {
+                return Ok(());
+            }
+            handle.join().unwrap_or_else(|_| Err(std::io::Error::other("a stream of the child was lost")))
+        }
+        fn read(stream: Option<Drained>) -> std::io::Result<Vec<u8>> {
+            match stream {
+                Some((handle, kept)) => {
+                    join(handle)?;
+                    let mut kept = kept.lock().unwrap_or_else(PoisonError::into_inner);
+                    Ok(std::mem::take(&mut *kept))
+                },
+                None => Ok(Vec::new())
+            }
+        }
+        self.stdin.map(join).transpose()?;
+        Ok(Output {
+            stdout: read(self.stdout)?,
+            stderr: read(self.stderr)?,
+            overflow: self.overflow.is_raised()
+        })
+    }
+}
+
+/// Reads the pipe until it is closed, keeping the first `max` bytes. Raises `overflow` as soon as there are more.
+fn drain<R: Read>(mut pipe: R, max: u64, kept: &Mutex<Vec<u8>>, overflow: &Alarm) -> std::io::Result<()> {
+    let max = usize::try_from(max).unwrap_or(usize::MAX);
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match pipe.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        };
+        let mut kept = kept.lock().unwrap_or_else(PoisonError::into_inner);
+        let room = max.saturating_sub(kept.len()).min(read);
+        kept.extend_from_slice(&buffer[..room]);
+        if room < read {
+            break;
+        }
+    }
+    overflow.raise();
+    // The rest is discarded, so that the child does not block writing it
+    std::io::copy(&mut pipe, &mut std::io::sink())?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use tempdir::TempDir;
+
+    /// Runs the shell script in the sandbox, or returns `None` where unprivileged user namespaces are not available
+    fn sandboxed(script: &str, dir: &Path) -> Option<ExitStatus> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script).current_dir(dir).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        sandbox(&mut command, &Sandbox{max_processes: None}, dir).unwrap();
+        match command.status() {
+            Ok(status) => Some(status),
+            Err(e) => {
+                eprintln!("Skipping a sandbox test, {}", e);
+                None
+            }
+        }
+    }
+
+    fn spawn_group(script: &str) -> Child {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        new_group(&mut command);
+        command.spawn().unwrap()
+    }
+
+    fn spawn_piped(script: &str) -> Child {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        new_group(&mut command);
+        command.spawn().unwrap()
+    }
+
+    #[test]
+    fn streams_feed_the_input_and_keep_the_output() {
+        let input: Vec<u8> = (0..200_000).map(|idx| b'a' + (idx % 26) as u8).collect();
+        let mut child = spawn_piped("cat; echo done >&2");
+        let streams = Streams::start(&mut child, Some(input.clone()), 1 << 20, 1 << 20);
+        let (status, _usage, _leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().unwrap();
+        assert!(status.success());
+        let output = streams.finish().unwrap();
+        assert_eq!(output.stdout, input);
+        assert_eq!(output.stderr, b"done\n");
+        assert!(!output.overflow);
+    }
+
+    #[test]
+    fn streams_stop_at_their_limit() {
+        // The overflow stops the wait right away, even if the program would never end
+        let mut child = spawn_piped("exec yes");
+        let streams = Streams::start(&mut child, None, 1000, 1000);
+        let start = Instant::now();
+        assert!(wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        kill_group(&mut child).unwrap();
+        let output = streams.finish().unwrap();
+        assert_eq!(output.stdout.len(), 1000);
+        assert!(output.stdout.starts_with(b"y\ny\n"));
+        assert!(output.overflow);
+
+        // Only the stdout counts as an overflow, a long stderr is just cut
+        let mut child = spawn_piped("head -c 5000 /dev/zero >&2; echo ok");
+        let streams = Streams::start(&mut child, Some(Vec::new()), 1000, 100);
+        assert!(wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().is_some());
+        let output = streams.finish().unwrap();
+        assert_eq!((output.stdout.as_slice(), output.stderr.len(), output.overflow), (&b"ok\n"[..], 100, false));
+    }
+
+    #[test]
+    fn streams_held_outside_of_the_group_are_given_up() {
+        // The shell gives the escaped process time to leave the group before it ends and the group is killed
+        let mut child = spawn_piped("setsid sh -c 'echo started; exec sleep 4' & sleep 0.5");
+        let streams = Streams::start(&mut child, None, 1000, 1000);
+        let (_status, _usage, leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().unwrap();
+        assert!(!leftovers);
+        let start = Instant::now();
+        let output = streams.finish().unwrap();
+        assert_eq!(output.stdout, b"started\n");
+        assert!(start.elapsed() >= STREAMS_GRACE && start.elapsed() < STREAMS_GRACE + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn waits_block_until_exit_or_timeout() {
+        let mut child = spawn_group("sleep 0.2");
+        let start = Instant::now();
+        let (status, _usage, _leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), None, &Alarm::new()).unwrap().unwrap();
+        assert!(status.success());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let mut child = spawn_group("sleep 10");
+        let start = Instant::now();
+        assert!(wait_timeout(&mut child, Duration::from_millis(100)).unwrap().is_none());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        kill_group(&mut child).unwrap();
+    }
+
+    #[test]
+    fn leftovers_are_detected_and_killed() {
+        // The background sleep still holds the stdout, so the streams only end once it is killed
+        let mut child = spawn_piped("sleep 5 & exit 0");
+        let streams = Streams::start(&mut child, None, 1000, 1000);
+        let start = Instant::now();
+        let (status, _usage, leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().unwrap();
+        assert!(status.success() && leftovers);
+        streams.finish().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let mut child = spawn_piped("exit 0");
+        let streams = Streams::start(&mut child, None, 1000, 1000);
+        let (_status, _usage, leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), None, streams.overflow()).unwrap().unwrap();
+        assert!(!leftovers);
+        streams.finish().unwrap();
+    }
+
+    #[test]
+    fn killing_a_group_kills_every_process() {
+        let mut child = spawn_piped("sleep 5 & sleep 5");
+        let streams = Streams::start(&mut child, None, 1000, 1000);
+        let start = Instant::now();
+        assert!(wait_group_timeout(&mut child, Duration::from_millis(100), None, streams.overflow()).unwrap().is_none());
+        let pgid = child.id() as libc::pid_t;
+        kill_group(&mut child).unwrap();
+        assert!(child.try_wait().unwrap().is_some());
+        streams.finish().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        // Killed members that are not reaped yet are zombies, which have no resident memory
+        assert!(group_members(pgid).unwrap().iter().all(|member| member.resident == 0));
+    }
+
+    #[test]
+    fn raised_alarms_stop_waiting() {
+        let mut child = spawn_group("sleep 10");
+        let alarm = Arc::new(Alarm::new());
+        let raiser = alarm.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            raiser.raise();
+        });
+        let start = Instant::now();
+        assert!(wait_group_timeout(&mut child, Duration::from_secs(10), None, &alarm).unwrap().is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        kill_group(&mut child).unwrap();
+    }
+
+    #[test]
+    fn memory_limits_cover_the_whole_group() {
+        // A shell holding 25 MB peaks at about 50 MB, so only three of them together go over the limit
+        let hold = "hold() { x=$(head -c 25000000 /dev/zero | tr '\\0' a); sleep $1; }";
+        let limit = MemoryLimit{max_ram: 64 * 1024 * 1024, sandboxed: false};
+        let mut child = spawn_group(&format!("{}; hold 0", hold));
+        let (status, usage, _leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), Some(limit), &Alarm::new()).unwrap().unwrap();
+        assert!(status.success() && !usage.out_of_memory);
+
+        let mut child = spawn_group(&format!("{}; hold 10 & hold 10 & hold 10", hold));
+        let start = Instant::now();
+        let (status, usage, _leftovers) = wait_group_timeout(&mut child, Duration::from_secs(10), Some(limit), &Alarm::new()).unwrap().unwrap();
+        assert_eq!(signal(&status), Some(libc::SIGKILL));
+        assert!(usage.out_of_memory && usage.peak_ram.unwrap() > limit.max_ram);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn sandbox_keeps_signals() {
+        let dir = TempDir::new("sandbox").unwrap();
+        if let Some(status) = sandboxed("kill -ABRT $$", dir.path()) {
+            assert_eq!(signal(&status), Some(libc::SIGABRT));
+            let status = sandboxed("kill -PIPE $$", dir.path()).unwrap();
+            assert_eq!(signal(&status), Some(libc::SIGPIPE));
+            assert_eq!(sandboxed("exit 3", dir.path()).unwrap().code(), Some(3));
+            // The program is not the init of the namespace
+            assert_eq!(sandboxed("test $$ -ne 1", dir.path()).unwrap().code(), Some(0));
+        }
+    }
+
+    #[test]
+    fn sandbox_only_writes_in_its_directory() {
+        let dir = TempDir::new("sandbox").unwrap();
+        // Anyone could write in the other directory, were it not for the read-only mount
+        let outside = TempDir::new("outside").unwrap();
+        std::fs::set_permissions(outside.path(), std::os::unix::fs::PermissionsExt::from_mode(0o777)).unwrap();
+        let script = format!("echo inside > inside && touch {}/escaped 2>&1 | grep -q 'Read-only'", outside.path().display());
+        if let Some(status) = sandboxed(&script, dir.path()) {
+            assert_eq!(status.code(), Some(0));
+            assert_eq!(std::fs::read_to_string(dir.path().join("inside")).unwrap(), "inside\n");
+            assert!(!outside.path().join("escaped").exists());
+        }
+    }
+
+    #[test]
+    fn sandbox_has_no_network() {
+        let dir = TempDir::new("sandbox").unwrap();
+        // The only interface of a new network namespace is the loopback one
+        if let Some(status) = sandboxed("test \"$(grep ':' /proc/net/dev | cut -d: -f1 | tr -d ' ')\" = lo", dir.path()) {
+            assert_eq!(status.code(), Some(0));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Syscall filters that the language settings can apply to the programs of the competitors
+#[cfg(target_os = "linux")]
+extern crate libc;
+
+use std::process::Command;
+
+/// Names of the profiles that can be referenced from the language settings
+pub const PROFILES: [&str; 2] = ["compiled-native", "python-interpreter"];
+
+/// Syscalls that only the administration of a system needs, denied by every profile
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ADMINISTRATION: &[libc::c_long] = &[
+    libc::SYS_ptrace, libc::SYS_process_vm_readv, libc::SYS_process_vm_writev,
+    libc::SYS_mount, libc::SYS_umount2, libc::SYS_pivot_root, libc::SYS_chroot,
+    libc::SYS_fsopen, libc::SYS_fsconfig, libc::SYS_fsmount, libc::SYS_fspick, libc::SYS_move_mount, libc::SYS_open_tree, libc::SYS_mount_setattr,
+    libc::SYS_unshare, libc::SYS_setns,
+    libc::SYS_reboot, libc::SYS_kexec_load, libc::SYS_kexec_file_load,
+    libc::SYS_init_module, libc::SYS_finit_module, libc::SYS_delete_module,
+    libc::SYS_swapon, libc::SYS_swapoff, libc::SYS_acct, libc::SYS_quotactl, libc::SYS_syslog,
+    libc::SYS_settimeofday, libc::SYS_clock_settime, libc::SYS_clock_adjtime, libc::SYS_adjtimex,
+    libc::SYS_sethostname, libc::SYS_setdomainname,
+    libc::SYS_bpf, libc::SYS_perf_event_open, libc::SYS_userfaultfd, libc::SYS_fanotify_init,
+    libc::SYS_keyctl, libc::SYS_add_key, libc::SYS_request_key,
+    libc::SYS_open_by_handle_at, libc::SYS_name_to_handle_at,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_iopl,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_ioperm
+];
+
+/// Syscalls that reach the network, denied by every profile
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const NETWORK: &[libc::c_long] = &[
+    libc::SYS_socket, libc::SYS_connect, libc::SYS_bind, libc::SYS_listen, libc::SYS_accept, libc::SYS_accept4
+];
+
+/// Syscalls that start new processes, denied to compiled programs
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const PROCESSES: &[libc::c_long] = &[libc::SYS_fork, libc::SYS_vfork];
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const PROCESSES: &[libc::c_long] = &[];
+
+/// `AUDIT_ARCH_*` value of the architecture, missing in libc
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const AUDIT_ARCH: u32 = 0xc000003e;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const AUDIT_ARCH: u32 = 0xc00000b7;
+
+/// Offsets of `struct seccomp_data`
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const NR_OFFSET: u32 = 0;
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ARCH_OFFSET: u32 = 4;
+/// Lower half of the first argument, in little endian machines
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const FIRST_ARGUMENT_OFFSET: u32 = 16;
+
+/// Installs the profile right before the process spawned by the command executes its program. A process calling a
+/// denied syscall is killed with `SIGSYS`.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn install(command: &mut Command, profile: &str) -> std::io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let filter = filter(profile).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unknown seccomp profile \"{}\"", profile)))?;
+    unsafe {
+        command.pre_exec(move || {
+            let program = libc::sock_fprog {
+                len: filter.len() as libc::c_ushort,
+                filter: filter.as_ptr() as *mut libc::sock_filter
+            };
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &program as *const libc::sock_fprog) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+pub fn install(_command: &mut Command, _profile: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "seccomp profiles are only supported on linux, in x86_64 and aarch64"))
+}
+
+/// Indicates if the signal that ended a process is the one sent by a seccomp filter
+#[cfg(target_os = "linux")]
+pub fn is_violation(signal: Option<i32>) -> bool {
+    signal == Some(libc::SIGSYS)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_violation(_signal: Option<i32>) -> bool {
+    false
+}
+
+/// Builds the BPF program of a profile
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn filter(profile: &str) -> Option<Vec<libc::sock_filter>> {
+    let statement = |code: u32, k: u32| libc::sock_filter{code: code as u16, jt: 0, jf: 0, k};
+    let jump = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter{code: code as u16, jt, jf, k};
+    let kill = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS);
+    let allow = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW);
+
+    let native = match profile {
+        "compiled-native" => true,
+        "python-interpreter" => false,
+        _ => return None
+    };
+
+    // Syscalls of other architectures are not checked, so they are not allowed at all
+    let mut program = vec![
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET),
+        jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, AUDIT_ARCH, 1, 0),
+        kill,
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET)
+    ];
+    // The x32 ABI shares the architecture of x86_64, with its own syscall numbers
+    if cfg!(target_arch = "x86_64") {
+        program.push(jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, 0x40000000, 0, 1));
+        program.push(kill);
+    }
+
+    let denied = ADMINISTRATION.iter().chain(NETWORK.iter()).chain(PROCESSES.iter().filter(|_| native));
+    for syscall in denied {
+        program.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, *syscall as u32, 0, 1));
+        program.push(kill);
+    }
+
+    if native {
+        // clone3 can not be inspected, so it fails and the C library falls back to clone, which is only allowed
+        // for new threads
+        program.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, libc::SYS_clone3 as u32, 0, 1));
+        program.push(statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+        program.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, libc::SYS_clone as u32, 0, 4));
+        program.push(statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, FIRST_ARGUMENT_OFFSET));
+        program.push(jump(libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K, libc::CLONE_THREAD as u32, 0, 1));
+        program.push(allow);
+        program.push(kill);
+    }
+    program.push(allow);
+    Some(program)
+}
+
+#[cfg(test)]
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Stdio};
+
+    fn run(profile: &str, program: &str, arguments: &[&str]) -> ExitStatus {
+        let mut command = Command::new(program);
+        command.args(arguments).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        install(&mut command, profile).unwrap();
+        command.status().unwrap()
+    }
+
+    #[test]
+    fn profiles_deny_their_syscalls() {
+        for profile in PROFILES {
+            assert!(run(profile, "sh", &["-c", "exit 0"]).success(), "{}", profile);
+            // Entering new namespaces is harmless, and the filter kills it anyway
+            assert!(is_violation(run(profile, "unshare", &["--user", "true"]).signal()), "{}", profile);
+        }
+        // Pipelines of the shell need new processes
+        assert!(is_violation(run("compiled-native", "sh", &["-c", "true | true"]).signal()));
+        assert!(run("python-interpreter", "sh", &["-c", "true | true"]).success());
+    }
+
+    #[test]
+    fn interpreters_have_no_network() {
+        let python = Command::new("python3").arg("--version").stdout(Stdio::null()).status();
+        if python.map(|status| status.success()).unwrap_or(false) {
+            assert!(run("python-interpreter", "python3", &["-c", "print(1)"]).success());
+            assert!(is_violation(run("python-interpreter", "python3", &["-c", "import socket; socket.socket()"]).signal()));
+        }
+    }
+
+    #[test]
+    fn unknown_profiles_are_rejected() {
+        assert!(install(&mut Command::new("true"), "anything").is_err());
+    }
+}
@@ -2,7 +2,10 @@ extern crate serde;
 
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
-use crate::{Proposal, Scenario, Language, LanguageSettings};
+use std::io;
+use std::path::Path;
+use crate::course;
+use crate::{Proposal, Scenario, Clock, Language, LanguageSettings};
 
 #[derive(Clone)]
 pub struct Competitor {
@@ -11,11 +14,73 @@ pub struct Competitor {
     pub result: Option<String>
 }
 
+/// Outcome of a competitor's program in a single scenario
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type")]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    TimeLimit,
+    MemoryLimit,
+    RuntimeError {
+        exit_code: Option<i32>,
+        signal: Option<i32>
+    },
+    OutputLimit,
+    /// Only some of the points were earned, according to a checker program
+    Partial,
+    /// The checker program could not decide over the output
+    CheckerFailure,
+    /// The program was killed for calling a syscall denied by the seccomp profile of its language
+    SecurityViolation
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Verdict::Accepted => write!(formatter, "accepted"),
+            Verdict::WrongAnswer => write!(formatter, "wrong answer"),
+            Verdict::TimeLimit => write!(formatter, "time limit exceeded"),
+            Verdict::MemoryLimit => write!(formatter, "memory limit exceeded"),
+            Verdict::RuntimeError{exit_code: Some(code), ..} => write!(formatter, "runtime error (exit code {})", code),
+            Verdict::RuntimeError{signal: Some(signal), ..} => write!(formatter, "runtime error (signal {})", signal),
+            Verdict::RuntimeError{..} => write!(formatter, "runtime error"),
+            Verdict::OutputLimit => write!(formatter, "output limit exceeded"),
+            Verdict::Partial => write!(formatter, "partially accepted"),
+            Verdict::CheckerFailure => write!(formatter, "checker failure"),
+            Verdict::SecurityViolation => write!(formatter, "security violation")
+        }
+    }
+}
+
+/// Verdict and measurements of a single scenario
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScenarioResult {
+    pub verdict: Verdict,
+    /// Wall time, in seconds
+    pub wall_time: f64,
+    /// User and system cpu time, in seconds, when it could be measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time: Option<f64>,
+    /// Peak Ram used, in bytes
+    pub peak_ram: Option<u64>,
+    /// Points earned in the scenario
+    pub points: f64,
+    /// Comment left by the checker program
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Evaluation {
     Grade {
-        score: f64
+        score: f64,
+        scenarios: Vec<ScenarioResult>
+    },
+    CompileError {
+        /// Error output of the failing compilation step, truncated if too long
+        stderr: String
     },
     RunError,
     NoFile
@@ -28,23 +93,47 @@ pub struct Settings {
 }
 
 impl Settings {
+    /// Reads a settings file, or a course directory with a directory for every problem
+    pub fn load(path: &Path) -> io::Result<Settings> {
+        if path.is_dir() {
+            course::load(path)
+        } else {
+            Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+        }
+    }
+
     /// Generates an example configuration for a problem written in shell
     pub fn example() -> Settings {
+        let shell = Language::new("Shell");
         Settings {
             proposals: vec![("problem-a".to_string(), Proposal {
                 scenarios: vec![Scenario {
                     arguments: Some(vec!["Tito".to_string()]),
+                    env: HashMap::new(),
+                    fixtures: Vec::new(),
                     input: None,
+                    generator: None,
+                    input_file: None,
                     output: None,
+                    output_file: None,
+                    checker: None,
                     max_time: 1.0,
                     max_ram: None,
+                    max_output: None,
+                    peak_ram: None,
                     points: 10
                 }],
                 solution: "./problem-a.sh".to_string(),
-                language: Language::Shell,
+                filename: None,
+                language: shell.clone(),
+                checker: None,
+                interactor: None,
+                clock: Clock::Wall,
+                fixtures: Vec::new(),
+                generators: HashMap::new(),
                 points: 10
             })].into_iter().collect(),
-            language_settings: Some(vec![(Language::Shell, Language::Shell.default_settings())].into_iter().collect())
+            language_settings: shell.default_settings().map(|language_settings| vec![(shell.clone(), language_settings)].into_iter().collect())
         }
     }
 }
\ No newline at end of file
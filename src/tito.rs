@@ -1,151 +1,669 @@
+extern crate base64;
 extern crate log;
 extern crate tempdir;
-extern crate wait_timeout;
 
 use crate::{
-    Settings, Arena, Competitor, Proposal, Problem, Language, LanguageSettings, Scenario, Evaluation
+    Settings, Arena, Competitor, Proposal, Problem, Program, Protocol, Language, LanguageSettings, Scenario, Clock, Fixture, Evaluation, Verdict, ScenarioResult,
+    Checker, Tournament, TournamentResult, Match, Cache
 };
-use wait_timeout::ChildExt;
+use crate::process::{self, Sandbox};
+use crate::{cache, seccomp};
+use crate::tournament::Table;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use tempdir::TempDir;
 use std::fs::File;
 use std::io::prelude::*;
-use std::collections::{HashMap, HashSet};
-use std::path::{PathBuf};
-use std::process::{Command, Stdio};
+use std::io::BufReader;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Maximum size, in bytes, of the compiler diagnostics kept for a competitor
+const MAX_DIAGNOSTICS: usize = 8 * 1024;
+/// Maximum size, in bytes, of the message of a checker program kept for a competitor
+const MAX_CHECKER_MESSAGE: usize = 1024;
+/// Maximum time a checker program can take to judge a single output
+const CHECKER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum time a generator can take to write the input of a scenario
+const GENERATOR_TIMEOUT: Duration = Duration::from_secs(60);
+/// Exit code of testlib's `_pc(0)`, partial credit codes go from it up
+const TESTLIB_PC_BASE: i32 = 50;
+/// Time the interactor is given to finish once the program ended, if the time limit is already over
+const INTERACTOR_GRACE: Duration = Duration::from_secs(1);
+/// Maximum size, in bytes, of the log kept for a single match of a tournament
+const MAX_TRANSCRIPT: usize = 1024 * 1024;
+/// Maximum size, in bytes, of the output of a program in a scenario that does not set its own
+const DEFAULT_MAX_OUTPUT: u64 = 64 * 1024 * 1024;
+/// Maximum size, in bytes, of the stderr kept for any program, the rest is discarded
+const MAX_STDERR: u64 = 64 * 1024;
+/// Environment every program of a competitor starts from, so that runs are the same on any grader
+const FIXED_ENV: [(&str, &str); 5] = [
+    ("LANG", "C.UTF-8"),
+    ("TZ", "UTC"),
+    ("PYTHONHASHSEED", "0"),
+    ("PERL_HASH_SEED", "0"),
+    ("PERL_PERTURB_KEYS", "0")
+];
+/// Maximum time a pre tool can take when its settings do not give one
+const DEFAULT_TOOL_TIME: Duration = Duration::from_secs(60);
+/// Times `max_time` a program can run, in wall time, when only its cpu time is limited
+const CPU_WALL_FACTOR: u32 = 3;
+
+/// Result of running the main tool in a single scenario
+struct Execution {
+    /// Standard output of the program, or the reason it could not be obtained
+    output: Result<String, Error>,
+    /// Peak resident memory, in bytes, when the platform reports it
+    peak_ram: Option<u64>,
+    /// Time elapsed between the start of the program and its end
+    wall_time: Duration,
+    /// User and system cpu time, when the platform reports it
+    cpu_time: Option<Duration>,
+    /// Decision of the interactor, for interactive problems
+    interaction: Option<Judgement>
+}
+
+/// Main tool of a program whose pre tools already ran, ready to be executed
+struct Executable {
+    utility: String,
+    arguments: Vec<String>,
+    /// Working directory of the program
+    dir: PathBuf,
+    /// Name of the source file in `dir`, when the program does not need it to run
+    source: Option<String>,
+    /// Seccomp profile applied when the program belongs to a competitor
+    seccomp: Option<String>,
+    /// Whole environment of the program
+    env: HashMap<String, String>
+}
+
+/// Decision taken over the output of a single scenario
+struct Judgement {
+    verdict: Verdict,
+    /// Fraction of the points of the scenario that were earned
+    score: f64,
+    /// Comment left by a checker program
+    message: Option<String>
+}
+
+/// Something that happened during a match of a tournament
+enum MatchEvent {
+    /// Line printed by the referee, or `None` once it stops printing
+    Referee(Option<String>),
+    /// Line printed by the bot in the given seat, or `None` once it stops printing
+    Bot(usize, Option<String>),
+    /// Line in the stderr of a bot, or of the referee if there is no seat
+    Stderr(Option<usize>, Option<String>)
+}
+
+/// Messages exchanged during a match, kept up to a maximum size, each one with the seat of the only bot that can
+/// read it, if any. The organizer reads everything.
+struct Transcript {
+    start: Instant,
+    lines: Vec<(Audience, String)>,
+    size: usize
+}
+
+/// Who, besides the organizer, can read a line of a transcript
+#[derive(Clone, Copy, PartialEq)]
+enum Audience {
+    Everyone,
+    Seat(usize),
+    Organizer
+}
+
+impl Transcript {
+    fn new() -> Transcript {
+        Transcript{start: Instant::now(), lines: Vec::new(), size: 0}
+    }
+
+    /// Notes a line that every bot can read
+    fn note(&mut self, text: String) {
+        self.note_for(Audience::Everyone, text);
+    }
+
+    fn note_for(&mut self, audience: Audience, text: String) {
+        if self.size > MAX_TRANSCRIPT {
+            return;
+        }
+        let line = format!("[{:8.3}] {}", self.start.elapsed().as_secs_f64(), text);
+        self.size += line.len();
+        self.lines.push((audience, line));
+        if self.size > MAX_TRANSCRIPT {
+            self.lines.push((Audience::Everyone, "[...]".to_string()));
+        }
+    }
+
+    /// Lines the bot in the seat can read, or all of them for the organizer
+    fn view(&self, seat: Option<usize>) -> String {
+        let lines = self.lines.iter().filter(|(audience, _)| match (seat, audience) {
+            (None, _) | (_, Audience::Everyone) => true,
+            (Some(seat), Audience::Seat(reader)) => seat == *reader,
+            (Some(_), Audience::Organizer) => false
+        });
+        lines.map(|(_, line)| format!("{}\n", line)).collect()
+    }
+}
 
 pub struct Tito {
     language_settings: HashMap<Language, LanguageSettings>,
-    dir: TempDir
-} 
+    dir: TempDir,
+    /// Number of competitor files graded at the same time
+    workers: usize,
+    /// Indicates if every worker, and the programs it runs, is kept in a single cpu
+    pin_cpus: bool,
+    /// Previous compilations, reused for identical sources and toolchains
+    cache: Option<Cache>,
+    /// Isolation of the programs of the competitors, if any
+    sandbox: Option<Sandbox>
+}
 
 impl Tito {
     pub fn new() -> Result<Tito, Error> {
-        let dir = TempDir::new("tito").map_err(|e| Error::IOError(e))?;
+        let dir = TempDir::new("tito").map_err(Error::IOError)?;
         Ok(Tito {
             language_settings: HashMap::new(),
-            dir
+            dir,
+            workers: 1,
+            pin_cpus: false,
+            cache: None,
+            sandbox: None
         })
     }
 
-    pub fn build(&mut self, settings: Settings) -> Result<Arena, Error> {
-        let languages: HashSet<_> = settings.proposals.values().map(|proposal| proposal.language.clone()).collect();
+    /// Sets how many competitor files are graded at the same time, one by default
+    pub fn with_workers(mut self, workers: usize) -> Tito {
+        self.workers = workers.max(1);
+        self
+    }
 
-        log::info!("Gathering languages information...");
-        self.gather_language_info(languages)?;
+    /// Keeps every worker in its own cpu, so that parallel grading disturbs time measurements less
+    pub fn with_cpu_pinning(mut self, pin_cpus: bool) -> Tito {
+        self.pin_cpus = pin_cpus;
+        self
+    }
 
-        // Last but obviously not least, we test proposal codes
-        let problems: HashMap<_, _> = settings.proposals.iter().map(|(name, proposal)| {
-            log::info!("Evaluating \"{}\"", name);
+    /// Reuses compilations stored in the cache, and stores new ones in it. No cache is used by default.
+    pub fn with_cache(mut self, cache: Option<Cache>) -> Tito {
+        self.cache = cache;
+        self
+    }
+
+    /// Runs the programs of the competitors in a sandbox. They run with the privileges of the grader by default.
+    pub fn with_sandbox(mut self, sandbox: Option<Sandbox>) -> Tito {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Builds the arena of the settings, whose fixtures are relative to `base`
+    pub fn build(&mut self, settings: Settings, base: &Path) -> Result<Arena, Error> {
+        // Problems are first laid out with the programs of the instructor embedded, but without solutions
+        let mut problems: HashMap<_, _> = settings.proposals.iter().map(|(name, proposal)| {
             // We extract the filename
-            let filename = match PathBuf::from(&proposal.solution).as_path().file_stem() {
-                Some(os_str) => os_str.to_string_lossy().to_string(),
-                None => return Err(Error::NoFileName(name.clone()))
+            let filename = match (&proposal.filename, PathBuf::from(&proposal.solution).as_path().file_stem()) {
+                (Some(filename), _) => filename.clone(),
+                (None, Some(os_str)) => os_str.to_string_lossy().to_string(),
+                (None, None) => return Err(Error::NoFileName(name.clone()))
             };
-            // We test the proposal in the corresponding scenarios to get the solutions
-            let solutions = self.test_proposal(proposal)?;
-            
-            // We alter the 
-            let scenarios = proposal.scenarios.iter().zip(solutions.iter()).map(|(sc, so)| {
+            // Checkers are validated before anything runs
+            for checker in proposal.checker.iter().chain(proposal.scenarios.iter().filter_map(|scenario| scenario.checker.as_ref())) {
+                if let Err(detail) = checker.validate() {
+                    return Err(Error::InvalidChecker(name.clone(), detail));
+                }
+            }
+
+            let scenarios = proposal.scenarios.iter().enumerate().map(|(idx, sc)| {
                 let mut sc = sc.clone();
-                sc.output = Some(so.trim().into());
-                sc
-            }).collect();
+                if (sc.input.is_some() && sc.input_file.is_some()) || (sc.output.is_some() && sc.output_file.is_some()) {
+                    return Err(Error::TestDataError(format!("scenario {} of \"{}\" has both inline and file data", idx, name)));
+                }
+                // Variables are checked like the ones of languages, but their values are used as they are
+                if let Some((variable, _)) = sc.env.iter().find(|(variable, value)| variable.is_empty() || variable.contains(['=', '\0']) || value.contains('\0')) {
+                    return Err(Error::TestDataError(format!("scenario {} of \"{}\" sets the invalid environment variable \"{}\"", idx, name, variable.escape_debug())));
+                }
+                if let Some(command) = &sc.generator {
+                    if sc.input.is_some() || sc.input_file.is_some() {
+                        return Err(Error::TestDataError(format!("scenario {} of \"{}\" has both an input and a generator", idx, name)));
+                    }
+                    let generator = command.split_whitespace().next().unwrap_or_default();
+                    if !proposal.generators.contains_key(generator) {
+                        return Err(Error::TestDataError(format!("scenario {} of \"{}\" uses the unknown generator \"{}\"", idx, name, generator)));
+                    }
+                }
+                // Test data files are found next to the settings, wherever the arena is built
+                for file in [&mut sc.input_file, &mut sc.output_file].into_iter().flatten() {
+                    *file = base.join(&*file).to_string_lossy().into_owned();
+                }
+                sc.checker = sc.checker.as_ref().map(Tito::embed_checker).transpose()?;
+                sc.fixtures = sc.fixtures.iter().map(|fixture| Tito::embed_fixture(fixture, base)).collect::<Result<_, _>>()?;
+                Ok(sc)
+            }).collect::<Result<_, _>>()?;
             Ok((name.clone(), Problem {
                 scenarios,
                 filename,
                 language: Some(proposal.language.clone()),
+                checker: proposal.checker.as_ref().map(Tito::embed_checker).transpose()?,
+                interactor: proposal.interactor.as_ref().map(Tito::embed_program).transpose()?,
+                solution: Some(Tito::embed_program(&Program {
+                    source: proposal.solution.clone(),
+                    language: proposal.language.clone(),
+                    code: None,
+                    protocol: Protocol::default(),
+                    arguments: Vec::new()
+                })?),
+                clock: proposal.clock,
+                fixtures: proposal.fixtures.iter().map(|fixture| Tito::embed_fixture(fixture, base)).collect::<Result<_, _>>()?,
                 points: proposal.points
             }))
         }).collect::<Result<_, _>>()?;
 
-        Ok(Arena{problems})
+        // Generators are only needed to build the arena, so they are kept apart from the problems
+        let generators: HashMap<_, HashMap<_, _>> = settings.proposals.iter().map(|(name, proposal)| {
+            let generators = proposal.generators.iter().map(|(generator, program)| Ok((generator.clone(), Tito::embed_program(program)?)));
+            Ok((name.clone(), generators.collect::<Result<_, _>>()?))
+        }).collect::<Result<_, _>>()?;
+
+        log::info!("Gathering languages information...");
+        let mut languages = Tito::languages(problems.values());
+        languages.extend(generators.values().flat_map(HashMap::values).map(|program| program.language.clone()));
+        self.gather_language_info(languages, &settings.language_settings.unwrap_or_default())?;
+
+        let programs = self.compile_programs(problems.values().flat_map(Tito::programs).chain(generators.values().flat_map(HashMap::values)))?;
+
+        // Generated inputs are written in the scenarios, so the arena does not need the generators
+        for (name, problem) in problems.iter_mut() {
+            for (idx, scenario) in problem.scenarios.iter_mut().enumerate() {
+                if let Some(command) = &scenario.generator {
+                    log::info!("Generating the input of scenario {} of \"{}\" with \"{}\"", idx, name, command);
+                    scenario.input = Some(self.generate(command, &generators[name], &programs)?);
+                }
+            }
+        }
+
+        // Last but obviously not least, we test proposal codes
+        for (name, proposal) in settings.proposals.iter() {
+            log::info!("Evaluating \"{}\"", name);
+            let problem = problems.get_mut(name).unwrap();
+            let interactor = problem.interactor.as_ref().map(|interactor| Tito::executable(&programs, interactor)).transpose()?;
+
+            // We test the proposal in the corresponding scenarios to get the solutions
+            let workspace = self.workspace()?;
+            let solutions = self.test_proposal(proposal, problem, interactor, workspace.path())?;
+
+            // We alter the scenarios with the solutions, unless the expected outputs were given
+            // Kattis interactors decide by themselves, without leaving an output to compare
+            let self_judged = problem.interactor.as_ref().map(|interactor| interactor.protocol == Protocol::Kattis).unwrap_or(false);
+            for (idx, (sc, (so, peak_ram))) in problem.scenarios.iter_mut().zip(solutions).enumerate() {
+                sc.peak_ram = peak_ram;
+                match Tito::expected_output(sc)? {
+                    Some(_) if self_judged => (),
+                    Some(expected) => {
+                        let judgement = self.judge(sc.checker.as_ref().or(problem.checker.as_ref()), &programs, sc, &so, &expected, workspace.path());
+                        if judgement.verdict != Verdict::Accepted {
+                            log::warn!("The solution of \"{}\" does not match the given output in scenario {}: {}", name, idx, judgement.verdict);
+                        }
+                    },
+                    None => sc.output = Some(so.trim().into())
+                }
+            }
+        }
+
+        // Checker programs should accept the reference solutions
+        for (name, problem) in problems.iter() {
+            for (idx, scenario) in problem.scenarios.iter().enumerate() {
+                let checker = scenario.checker.as_ref().or(problem.checker.as_ref());
+                // The answers of Kattis validators do not have to be valid outputs
+                if let (Some(Checker::Program(Program{protocol: Protocol::Testlib, ..})), Some(output)) = (checker, Tito::expected_output(scenario)?) {
+                    let workspace = self.workspace()?;
+                    let judgement = self.judge(checker, &programs, scenario, &output, &output, workspace.path());
+                    if judgement.verdict != Verdict::Accepted {
+                        log::warn!("The checker of \"{}\" does not accept the solution in scenario {}: {}", name, idx, judgement.verdict);
+                    }
+                }
+            }
+        }
+
+        Ok(Arena{problems, language_settings: self.language_settings.clone(), manifest: BTreeMap::new()})
     }
 
     pub fn run(&mut self, competitors: Vec<Competitor>, arena: Arena) -> Result<HashMap<String, HashMap<String, Evaluation>>, Error> {
-        let mut grades = HashMap::new();
-        let mut languages = HashSet::new();
+        // User grades for ever
+        let mut grades: HashMap<_, _> = competitors.iter().map(|competitor| (competitor.id.clone(), HashMap::new())).collect();
+
+        // The arena dictates the toolchain, so that competitors are graded with the one used for the solutions
+        log::info!("Gathering languages information...");
+        self.gather_language_info(Tito::languages(arena.problems.values()), &arena.language_settings)?;
+
+        let programs = self.compile_programs(arena.problems.values().flat_map(Tito::programs))?;
+        self.check_sandbox()?;
+
+        // Every job grades a competitor in a single problem, and workers take jobs until there are none left
+        let jobs: Vec<_> = competitors.iter().flat_map(|competitor| arena.problems.iter().map(move |(name, problem)| (competitor, name, problem))).collect();
+        let workers = self.workers.min(jobs.len()).max(1);
+        if self.pin_cpus && workers > std::thread::available_parallelism().map(|cpus| cpus.get()).unwrap_or(1) {
+            log::warn!("There are more workers than cpus, so some of them will share a cpu");
+        }
+        let next = AtomicUsize::new(0);
+        let (tito, jobs, next, programs) = (&*self, &jobs, &next, &programs);
+        let evaluations: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|worker| scope.spawn(move || tito.work(worker, jobs, next, programs))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))).collect()
+        });
+
+        for evaluations in evaluations {
+            for (id, name, evaluation) in evaluations? {
+                grades.entry(id).or_default().insert(name, evaluation);
+            }
+        }
+        Ok(grades)
+    }
 
-        for (_name, problem) in arena.problems.iter() {
-            languages.insert(problem.language.clone().unwrap());
+    /// Grades jobs from the list until there are none left, in its own cpu if pinning is enabled
+    fn work(&self, worker: usize, jobs: &[(&Competitor, &String, &Problem)], next: &AtomicUsize, programs: &HashMap<(Language, String), Executable>) -> Result<Vec<(String, String, Evaluation)>, Error> {
+        if self.pin_cpus {
+            match process::pin_to_cpu(worker) {
+                Ok(cpu) => log::info!("Worker {} pinned to cpu {}", worker, cpu),
+                Err(e) => log::warn!("Could not pin worker {} to a cpu, {}", worker, e)
+            }
+        }
+
+        let mut evaluations = Vec::new();
+        while let Some((competitor, name, problem)) = jobs.get(next.fetch_add(1, Ordering::SeqCst)) {
+            log::info!("Evaluating problem \"{}\" for competitor \"{}\"", name, competitor.id);
+            let graded = self.workspace().and_then(|workspace| self.grade(competitor, name, problem, programs, workspace.path()));
+            match graded {
+                Ok(evaluation) => evaluations.push((competitor.id.clone(), (*name).clone(), evaluation)),
+                Err(e) => {
+                    // The other workers stop as soon as they finish their current job
+                    next.store(jobs.len(), Ordering::SeqCst);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(evaluations)
+    }
+
+    /// Evaluates the file of the competitor for the problem, using the given workspace
+    fn grade(&self, competitor: &Competitor, name: &str, problem: &Problem, programs: &HashMap<(Language, String), Executable>, workspace: &Path) -> Result<Evaluation, Error> {
+        let interactor = problem.interactor.as_ref().map(|interactor| Tito::executable(programs, interactor)).transpose()?;
+        let executions = match self.evaluate(PathBuf::from(&competitor.files), problem, interactor, workspace) {
+            Ok(executions) => executions,
+            Err(e) => return Ok(Tito::failed_evaluation(e))
+        };
+
+        let mut score = 0.0;
+        let mut scenarios = Vec::new();
+
+        // Now, we compare them to give this guy a grade
+        for (idx, (execution, solution)) in executions.into_iter().zip(problem.scenarios.iter()).enumerate() {
+            let rejection = |verdict| Judgement{verdict, score: 0.0, message: None};
+            let judgement = match (execution.interaction, execution.output) {
+                // A rejection from the interactor explains failures of the program, like broken pipes
+                (Some(judgement), _) if judgement.verdict == Verdict::WrongAnswer => judgement,
+                (_, Err(Error::TimeExceeded)) => rejection(Verdict::TimeLimit),
+                (_, Err(Error::MemoryExceeded)) => rejection(Verdict::MemoryLimit),
+                (_, Err(Error::OutputExceeded)) => rejection(Verdict::OutputLimit),
+                (_, Err(Error::SecurityViolation)) => rejection(Verdict::SecurityViolation),
+                (_, Err(Error::RuntimeError{exit_code, signal, stderr})) => {
+                    log::info!("Scenario {} crashed, {}", idx, stderr);
+                    rejection(Verdict::RuntimeError{exit_code, signal})
+                },
+                (_, Err(e)) => {
+                    log::info!("Scenario {} failed, {}", idx, e);
+                    rejection(Verdict::WrongAnswer)
+                },
+                // As in Polygon, a checker has the last word over what an accepting interactor wrote
+                (Some(judgement), Ok(candidate)) => match solution.checker.as_ref().or(problem.checker.as_ref()) {
+                    Some(checker) if judgement.verdict == Verdict::Accepted => {
+                        let output = match Tito::expected_output(solution)? {
+                            Some(output) => output,
+                            None => return Err(Error::NoSolution(name.to_string(), idx))
+                        };
+                        self.judge(Some(checker), programs, solution, &candidate, &output, workspace)
+                    },
+                    _ => judgement
+                },
+                (None, Ok(candidate)) => {
+                    let output = match Tito::expected_output(solution)? {
+                        Some(output) => output,
+                        None => return Err(Error::NoSolution(name.to_string(), idx))
+                    };
+                    self.judge(solution.checker.as_ref().or(problem.checker.as_ref()), programs, solution, &candidate, &output, workspace)
+                }
+            };
+            let points = solution.points as f64 * judgement.score;
+            score += points;
+            scenarios.push(ScenarioResult {
+                verdict: judgement.verdict,
+                wall_time: execution.wall_time.as_secs_f64(),
+                cpu_time: execution.cpu_time.map(|cpu_time| cpu_time.as_secs_f64()),
+                peak_ram: execution.peak_ram,
+                points,
+                message: judgement.message
+            });
+        }
+
+        score /= problem.points as f64;
+        Ok(Evaluation::Grade{score, scenarios})
+    }
+
+    /// Plays a tournament between the bots of the competitors, writing the log of every match in `logs`
+    pub fn tournament(&mut self, competitors: Vec<Competitor>, tournament: Tournament, logs: &Path) -> Result<TournamentResult, Error> {
+        if let Err(detail) = tournament.validate() {
+            return Err(Error::InvalidTournament(detail));
         }
 
         log::info!("Gathering languages information...");
-        self.gather_language_info(languages)?;
-
-        for competitor in competitors {
-            // User grades for ever
-            let mut user_grades = HashMap::new();
-
-            for (name, problem) in arena.problems.iter() {
-                log::info!("Evaluating problem \"{}\" for competitor \"{}\"", name, competitor.id);
-                match self.evaluate(PathBuf::from(&competitor.files), problem) {
-                    Ok(mut outputs) => {
-                        let mut score = 0.0;
-                        // We will trim the answers
-                        for output in &mut outputs {
-                            *output = String::from(output.trim());
-                        }
+        let languages = vec![tournament.language.clone(), tournament.referee.language.clone()].into_iter().collect();
+        self.gather_language_info(languages, &tournament.language_settings.clone().unwrap_or_default())?;
 
-                        // Now, we compare them to give this guy a grade
-                        for (idx, (candidate, solution)) in outputs.iter().zip(problem.scenarios.iter()).enumerate() {
-                            if let Some(output) = &solution.output {
-                                if candidate == output {
-                                    score += solution.points as f64;
-                                }
-                            } else {
-                                return Err(Error::NoSolution(name.clone(), idx));
-                            }
-                        }
+        let referee = self.compile_program(&Tito::embed_program(&tournament.referee)?, "referee")?;
+        self.check_sandbox()?;
 
-                        score /= problem.points as f64;
+        let language_settings = match self.language_settings.get(&tournament.language) {
+            Some(v) => v.clone(),
+            None => return Err(Error::NoLangSettings(serde_json::to_string(&tournament.language).unwrap()))
+        };
+        let mut ids = Vec::new();
+        let mut bots = Vec::new();
+        let mut excluded = HashMap::new();
+        for (idx, competitor) in competitors.into_iter().enumerate() {
+            log::info!("Compiling the bot of competitor \"{}\"", competitor.id);
+            let mut path = PathBuf::from(self.dir.path());
+            path.push(format!("bot-{}", idx));
+            std::fs::create_dir_all(&path).map_err(Error::IOError)?;
+            let bot = Tito::find_source(Path::new(&competitor.files), &tournament.filename, &language_settings)
+                .and_then(|source| self.compile(&source, &language_settings, &path));
+            match bot {
+                Ok(bot) => {
+                    ids.push(competitor.id);
+                    bots.push(bot);
+                },
+                Err(e) => {
+                    excluded.insert(competitor.id, Tito::failed_evaluation(e));
+                }
+            }
+        }
+        if bots.len() < tournament.players {
+            return Err(Error::InvalidTournament(format!("only {} bots can play, but matches need {}", bots.len(), tournament.players)));
+        }
+        std::fs::create_dir_all(logs).map_err(Error::IOError)?;
+
+        let mut table = Table::new(ids.clone());
+        let mut matches = Vec::new();
+        for round in 0..tournament.rounds() {
+            let (groups, byes) = table.pairings(&tournament.format, tournament.players, round);
+            for player in byes {
+                log::info!("Round {}: bye for \"{}\"", round + 1, ids[player]);
+                table.bye(player, tournament.bye_points);
+                matches.push(Match{round: round + 1, players: vec![ids[player].clone()], scores: vec![tournament.bye_points], log: None, player_logs: Vec::new(), error: None});
+            }
+
+            for (idx, group) in groups.into_iter().enumerate() {
+                let players: Vec<String> = group.iter().map(|player| ids[*player].clone()).collect();
+                log::info!("Round {}: {}", round + 1, players.join(" vs "));
+                let seats: Vec<&Executable> = group.iter().map(|player| &bots[*player]).collect();
+                let mut transcript = Transcript::new();
+                let result = self.play_match(&referee, &seats, &tournament, &mut transcript);
 
-                        user_grades.insert(name.clone(), Evaluation::Grade{score});
+                // The whole log is for the organizer, and each player gets the part they can read
+                let filename = format!("round-{}-match-{}.log", round + 1, idx + 1);
+                std::fs::write(logs.join(&filename), transcript.view(None)).map_err(Error::IOError)?;
+                let mut player_logs = Vec::new();
+                for seat in 0..group.len() {
+                    let player_log = format!("round-{}-match-{}-seat-{}.log", round + 1, idx + 1, seat);
+                    std::fs::write(logs.join(&player_log), transcript.view(Some(seat))).map_err(Error::IOError)?;
+                    player_logs.push(player_log);
+                }
+
+                table.meet(&group);
+                let (scores, error) = match result {
+                    Ok(scores) => {
+                        table.record(&group, &scores);
+                        (scores, None)
                     },
-                    Err(e) => match e {
-                        Error::NoFileFound => {
-                            log::info!("File not found!");
-                            user_grades.insert(name.clone(), Evaluation::NoFile);
-                        },
-                        other => {
-                            log::warn!("{}", other);
-                            user_grades.insert(name.clone(), Evaluation::RunError);
-                        }
+                    Err(e) => {
+                        log::warn!("The match between {} does not count, {}", players.join(", "), e);
+                        (Vec::new(), Some(format!("{}", e)))
                     }
                 };
+                matches.push(Match{round: round + 1, players, scores, log: Some(filename), player_logs, error});
             }
+        }
+
+        let standings = table.ranking().into_iter().map(|player| table.standings[player].clone()).collect();
+        Ok(TournamentResult{standings, matches, excluded})
+    }
 
-            grades.insert(competitor.id.clone(), user_grades);
+    /// Isolates a program of a competitor in its own environment and process group, with the sandbox, if one was
+    /// requested, and the seccomp profile of its language, which is installed last so that setting up the sandbox is
+    /// not filtered. The working directory `dir` is the only one it can write to in the sandbox.
+    fn confine(&self, command: &mut Command, executable: &Executable, dir: &Path) -> Result<(), Error> {
+        command.env_clear().envs(&executable.env);
+        process::new_group(command);
+        if let Some(sandbox) = &self.sandbox {
+            process::sandbox(command, sandbox, dir).map_err(|e| Error::SandboxFailure(format!("{}", e)))?;
         }
-        Ok(grades)
+        if let Some(profile) = &executable.seccomp {
+            seccomp::install(command, profile).map_err(|e| Error::SandboxFailure(format!("{}", e)))?;
+        }
+        Ok(())
     }
 
-    fn gather_language_info(&mut self, languages: HashSet<Language>) -> Result<(), Error> {
+    /// Describes why a program of a competitor ended unsuccessfully
+    fn failure(executable: &Executable, status: ExitStatus, stderr: &[u8]) -> Error {
+        let signal = process::signal(&status);
+        if executable.seccomp.is_some() && seccomp::is_violation(signal) {
+            return Error::SecurityViolation;
+        }
+        Error::RuntimeError{
+            exit_code: status.code(),
+            signal,
+            stderr: String::from_utf8_lossy(stderr).to_string()
+        }
+    }
+
+    /// Memory limit of a program of a competitor or of a pre tool, which run in the sandbox if there is one
+    fn memory_limit(&self, max_ram: Option<u64>) -> Option<process::MemoryLimit> {
+        max_ram.map(|max_ram| process::MemoryLimit{max_ram, sandboxed: self.sandbox.is_some()})
+    }
+
+    /// Makes sure that the sandbox works in this system before any competitor is graded with it
+    fn check_sandbox(&self) -> Result<(), Error> {
+        let sandbox = match &self.sandbox {
+            Some(sandbox) => sandbox,
+            None => return Ok(())
+        };
+        if let Some(caveat) = process::sandbox_caveats(sandbox) {
+            log::warn!("Sandbox is incomplete, {}", caveat);
+        }
+        let mut command = Command::new("true");
+        command.current_dir(self.dir.path())
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::null());
+        process::sandbox(&mut command, sandbox, self.dir.path()).map_err(|e| Error::SandboxFailure(format!("{}", e)))?;
+        match command.status() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(Error::SandboxFailure(format!("a sandboxed process ended with {}", status))),
+            Err(e) => Err(Error::SandboxFailure(format!("{}", e)))
+        }
+    }
+
+    /// Creates a fresh directory for a single job, removed once dropped
+    fn workspace(&self) -> Result<TempDir, Error> {
+        TempDir::new_in(self.dir.path(), "job").map_err(Error::IOError)
+    }
+
+    /// Describes why a competitor's file could not be graded
+    fn failed_evaluation(e: Error) -> Evaluation {
+        match e {
+            Error::NoFileFound => {
+                log::info!("File not found!");
+                Evaluation::NoFile
+            },
+            Error::ToolFailure(idx, mut stderr) => {
+                log::info!("Compilation failed at tool {}", idx);
+                if truncate(&mut stderr, MAX_DIAGNOSTICS) {
+                    stderr += "\n[...]";
+                }
+                Evaluation::CompileError{stderr}
+            },
+            other => {
+                log::warn!("{}", other);
+                Evaluation::RunError
+            }
+        }
+    }
+
+    fn gather_language_info(&mut self, languages: HashSet<Language>, configured: &HashMap<Language, LanguageSettings>) -> Result<(), Error> {
         for language in languages.iter() {
-            log::info!("Checking default tools for language {}", serde_json::to_string(&language).unwrap());
-            let language_settings = LanguageSettings::default(language.clone());
+            let language_settings = match configured.get(language) {
+                Some(language_settings) => {
+                    log::info!("Checking configured tools for language {}", serde_json::to_string(&language).unwrap());
+                    language_settings.clone()
+                },
+                None => match language.default_settings() {
+                    Some(language_settings) => {
+                        log::info!("Checking default tools for language {}", serde_json::to_string(&language).unwrap());
+                        language_settings
+                    },
+                    None => return Err(Error::UnknownLanguage(language.to_string()))
+                }
+            };
+            if let Err(detail) = language_settings.validate() {
+                return Err(Error::InvalidLanguage(language.to_string(), detail));
+            }
 
             if let Some(pre_tools) = &language_settings.pre_tools {
                 for pre_tool in pre_tools {
                     if !pre_tool.temporal {
-                        match Tito::tool_exists(&pre_tool.utility) {
+                        // System-wide tools can only depend on custom placeholders
+                        let utility = language_settings.expand(&pre_tool.utility, "", "");
+                        match Tito::tool_exists(&utility) {
                             Ok(found) => if found {
-                                log::info!("Found pre-tool \"{}\"", &pre_tool.utility)
+                                log::info!("Found pre-tool \"{}\"", &utility)
                             } else {
-                                return Err(Error::MissingTool(format!("{}", &pre_tool.utility)));
+                                return Err(Error::MissingTool(utility));
                             },
-                            Err(e) => return Err(Error::ToolLookup(format!("{} ({})", e, &pre_tool.utility)))
+                            Err(e) => return Err(Error::ToolLookup(format!("{} ({})", e, &utility)))
                         }
                     }
                 }
             }
             if !language_settings.tool.temporal {
-                match Tito::tool_exists(&language_settings.tool.utility) {
+                let utility = language_settings.expand(&language_settings.tool.utility, "", "");
+                match Tito::tool_exists(&utility) {
                     Ok(found) => if found {
-                        log::info!("Found tool \"{}\"", &language_settings.tool.utility)
+                        log::info!("Found tool \"{}\"", &utility)
                     } else {
-                        return Err(Error::MissingTool(format!("{}", &language_settings.tool.utility)));
+                        return Err(Error::MissingTool(utility));
                     },
-                    Err(e) => return Err(Error::ToolLookup(format!("{} ({})", e, &language_settings.tool.utility)))
+                    Err(e) => return Err(Error::ToolLookup(format!("{} ({})", e, &utility)))
                 }
             }
             self.language_settings.insert(language.clone(), language_settings);
@@ -153,27 +671,15 @@ impl Tito {
         Ok(())
     }
 
-    fn evaluate(&self, directory: PathBuf, problem: &Problem) -> Result<Vec<String>, Error> {
+    fn evaluate(&self, directory: PathBuf, problem: &Problem, interactor: Option<&Executable>, workspace: &Path) -> Result<Vec<Execution>, Error> {
         // We lookup for the source code pointed in the proposal
         let (source, language_settings) = if let Some(language) = &problem.language {
-            let language_settings = match self.language_settings.get(&language) {
+            let language_settings = match self.language_settings.get(language) {
                 Some(v) => v.clone(),
                 None => return Err(Error::NoLangSettings(serde_json::to_string(&language).unwrap()))
             };
 
-            let mut filename = directory.clone();
-            filename.push(problem.filename.clone() + &format!(".{}", language_settings.extension));
-            let source = match File::open(&filename) {
-                Ok(mut f) => {
-                    let mut source = String::new();
-                    match f.read_to_string(&mut source) {
-                        Ok(_) => (),
-                        Err(e) => return Err(Error::IOError(e))
-                    }
-                    source
-                },
-                Err(_e) => return Err(Error::NoFileFound)
-            };
+            let source = Tito::find_source(&directory, &problem.filename, &language_settings)?;
             (source, language_settings)
         } else {
             // We guess with the file extension
@@ -181,13 +687,27 @@ impl Tito {
         };
 
         // We load the languae settings
-        let outputs = self.run_tools(source, problem.scenarios.clone(), &language_settings)?;
-        let mut result = Vec::new();
-        for output in outputs.into_iter() {
-            let output = output?;
-            result.push(output);
+        self.run_tools(source, problem, &language_settings, interactor, workspace)
+    }
+
+    /// Reads the file of a competitor, trying every extension of the language. The first existing file wins.
+    fn find_source(directory: &Path, filename: &str, language_settings: &LanguageSettings) -> Result<String, Error> {
+        let filename = language_settings.extensions().map(|extension| {
+            let mut path = directory.to_path_buf();
+            path.push(format!("{}.{}", filename, extension));
+            path
+        }).find(|path| path.is_file());
+        match filename.map(File::open) {
+            Some(Ok(mut f)) => {
+                let mut source = String::new();
+                match f.read_to_string(&mut source) {
+                    Ok(_) => (),
+                    Err(e) => return Err(Error::IOError(e))
+                }
+                Ok(source)
+            },
+            _ => Err(Error::NoFileFound)
         }
-        Ok(result)
     }
 
     fn tool_exists<T: AsRef<str>>(tool_name: T) -> Result<bool, String> {
@@ -204,21 +724,31 @@ impl Tito {
 
             Ok(output.status.success())
         } else {
-            let output = match Command::new("whereis")
-                .arg(tool_name.as_ref())
-                .stdin(Stdio::null()) 
-                .stderr(Stdio::null())
-                .stdout(Stdio::null())
-                .output() {
-                    Ok(v) => v,
-                    Err(e) => return Err(format!("{}", e))
-            };
+            // `whereis` succeeds even when it finds nothing, so we look in the PATH ourselves
+            let tool_name = tool_name.as_ref();
+            if tool_name.contains('/') {
+                return Ok(Tito::is_executable(Path::new(tool_name)));
+            }
+            let path = std::env::var_os("PATH").ok_or_else(|| "there is no PATH".to_string())?;
+            Ok(std::env::split_paths(&path).any(|dir| Tito::is_executable(&dir.join(tool_name))))
+        }
+    }
 
-            Ok(output.status.success())
+    /// Indicates if the path is a file that can be executed
+    fn is_executable(path: &Path) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::metadata(path).map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0).unwrap_or(false)
+        }
+        #[cfg(not(unix))]
+        {
+            path.is_file()
         }
     }
 
-    fn test_proposal(&self, proposal: &Proposal) -> Result<Vec<String>, Error> {
+    /// Runs the solution of the proposal in the scenarios of its problem, which has the fixtures embedded
+    fn test_proposal(&self, proposal: &Proposal, problem: &Problem, interactor: Option<&Executable>, workspace: &Path) -> Result<Vec<(String, Option<u64>)>, Error> {
         // We lookup for the source code pointed in the proposal
         let source = match File::open(&proposal.solution) {
             Ok(mut f) => {
@@ -238,11 +768,16 @@ impl Tito {
         };
         // We load the languae settings
         if let Some(language_setting) = self.language_settings.get(&proposal.language) {
-            let outputs = self.run_tools(source, proposal.scenarios.clone(), &language_setting)?;
+            let executions = self.run_tools(source, problem, language_setting, interactor, workspace)?;
             let mut result = Vec::new();
-            for output in outputs.into_iter() {
-                let output = output?;
-                result.push(output);
+            for (idx, execution) in executions.into_iter().enumerate() {
+                let output = execution.output?;
+                if let Some(judgement) = execution.interaction {
+                    if judgement.verdict != Verdict::Accepted {
+                        return Err(Error::SolutionRejected(idx, judgement.message.unwrap_or_else(|| judgement.verdict.to_string())));
+                    }
+                }
+                result.push((output, execution.peak_ram));
             }
             Ok(result)
         } else {
@@ -250,151 +785,1042 @@ impl Tito {
         }
     }
 
-    fn run_tools(&self, source: String, scenarios: Vec<Scenario>, language_settings: &LanguageSettings) -> Result<Vec<Result<String, Error>>, Error> {
-        // We will put all the tools in a single vector, and take note of the index of the main tool
-        let mut tools = Vec::new();
-        if let Some(pre_tools) = &language_settings.pre_tools {
-            tools = pre_tools.iter().map(|t| (t.clone(), false)).collect();
-        }
-        // Now we add the main one, the one that gives the results
-        tools.push((language_settings.tool.clone(), true));
+    /// Compiles the source in the `program` directory of the workspace and runs it in every scenario of the problem
+    fn run_tools(&self, source: String, problem: &Problem, language_settings: &LanguageSettings, interactor: Option<&Executable>, workspace: &Path) -> Result<Vec<Execution>, Error> {
+        let mut path = workspace.to_path_buf();
+        path.push("program");
+        std::fs::create_dir_all(&path).map_err(Error::IOError)?;
+        let executable = self.compile(&source, language_settings, &path)?;
+        self.run_scenarios(&executable, problem, interactor, workspace)
+    }
 
+    /// Writes the source code in the directory and executes the pre tools, returning the main tool
+    fn compile(&self, source: &str, language_settings: &LanguageSettings, path: &Path) -> Result<Executable, Error> {
         // We will write the source code, BAE
-        let path = PathBuf::from(self.dir.path());
-        let mut filename = path.clone();
-        filename.push(String::from("source.") + &language_settings.extension);
+        let mut filename = path.to_path_buf();
+        filename.push(language_settings.source_file());
 
         // Now we create the file
         match File::create(&filename) {
             Ok(mut f) => {
-                f.write_all(&source.as_bytes()).map_err(|e| Error::IOError(e))?;
+                f.write_all(source.as_bytes()).map_err(Error::IOError)?;
             },
             Err(e) => return Err(Error::IOError(e))
         };
 
+        let expand = |text: &str| language_settings.expand(text, &filename.to_string_lossy(), &path.to_string_lossy());
+
+        // Identical sources compiled with the same toolchain are taken from the cache
+        let pre_tools = language_settings.pre_tools.as_deref().unwrap_or_default();
+        let cached = match &self.cache {
+            Some(cache) if !pre_tools.is_empty() => {
+                let key = Cache::key(source, language_settings);
+                match cache.fetch(&key, path) {
+                    Ok(found) => Some((cache, key, found)),
+                    Err(e) => {
+                        log::warn!("Could not read compilation {} from the cache, {}", key, e);
+                        None
+                    }
+                }
+            },
+            _ => None
+        };
+        let pre_tools = match &cached {
+            Some((_, key, true)) => {
+                log::info!("Using cached compilation {}", key);
+                &[]
+            },
+            _ => pre_tools
+        };
+
+        // Now, tool execution, in the same clean environment as the programs they build
+        let env = Tito::environment(language_settings, expand);
+        for (idx, tool) in pre_tools.iter().enumerate() {
+            let args: Vec<String> = tool.arguments.iter().map(|arg| expand(arg)).collect();
+
+            let mut command = Command::new(expand(&tool.utility));
+            command.current_dir(path)
+                .args(&args)
+                .env_clear()
+                .envs(&env)
+                .stdin(Stdio::null()) // Para poder pasar argumentos al programa
+                .stderr(Stdio::piped()) // Para poder capturar la salida de error
+                .stdout(Stdio::null());
+            process::new_group(&mut command);
+            // Compilers read the code of the competitors, and their diagnostics are shown back, so they are confined
+            // like the programs they build, writing their temporary files in the directory of the compilation
+            if let Some(sandbox) = &self.sandbox {
+                command.env("TMPDIR", path);
+                process::sandbox(&mut command, sandbox, path).map_err(|e| Error::SandboxFailure(format!("{}", e)))?;
+            }
+            let mut child = match command.spawn() {
+                Ok(v) => v,
+                Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+            };
+
+            // A compilation that takes too long is a compile error, so it does not hold up the rest of the run
+            let streams = process::Streams::start(&mut child, None, 0, MAX_STDERR);
+            let max_time = tool.max_time.map(|max_time| Duration::from_millis((max_time * 1000.0) as u64)).unwrap_or(DEFAULT_TOOL_TIME);
+            let waited = match process::wait_group_timeout(&mut child, max_time, self.memory_limit(tool.max_ram), streams.overflow()) {
+                Ok(waited) => waited,
+                Err(e) => {
+                    if let Err(e) = process::kill_group(&mut child) {
+                        log::warn!("Could not kill process: {}", e);
+                    }
+                    return Err(Error::WaitTimeoutError(format!("{}", e)));
+                }
+            };
+            let (status, usage) = match waited {
+                Some((status, usage, _leftovers)) => (status, usage),
+                None => {
+                    if let Err(e) = process::kill_group(&mut child) {
+                        log::warn!("Could not kill process: {}", e);
+                    }
+                    return Err(Error::ToolFailure(idx, format!("the compilation exceeded the maximum time of {} s", max_time.as_secs_f32())));
+                }
+            };
+            let output = streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?;
+            if let (Some(max_ram), true) = (tool.max_ram, usage.out_of_memory) {
+                return Err(Error::ToolFailure(idx, format!("the compilation exceeded the maximum ram of {} bytes", max_ram)));
+            }
+            if !status.success() {
+                // Paths inside the working directory are shown relative to it, as students never see it
+                let stderr = String::from_utf8_lossy(&output.stderr).replace(&format!("{}/", path.to_string_lossy()), "");
+                return Err(Error::ToolFailure(idx, stderr));
+            }
+        }
+        if let Some((cache, key, false)) = &cached {
+            if let Err(e) = cache.store(key, path) {
+                log::warn!("Could not store compilation {} in the cache, {}", key, e);
+            }
+        }
+
+        // Now we prepare the main one, the one that gives the results
+        let utility = expand(&language_settings.tool.utility);
+        let arguments: Vec<String> = language_settings.tool.arguments.iter().map(|arg| expand(arg)).collect();
+        // Interpreters run the source, compiled programs do not need it anymore
+        let source_file = language_settings.source_file();
+        let runs_source = std::iter::once(&utility).chain(arguments.iter()).any(|part| part.contains(&source_file));
+        Ok(Executable {
+            utility,
+            arguments,
+            dir: path.to_path_buf(),
+            source: if runs_source { None } else { Some(source_file) },
+            seccomp: language_settings.seccomp.clone(),
+            env
+        })
+    }
+
+    /// Runs the program in every scenario of the problem, each time in a fresh `run` directory of the workspace
+    fn run_scenarios(&self, executable: &Executable, problem: &Problem, interactor: Option<&Executable>, workspace: &Path) -> Result<Vec<Execution>, Error> {
         let mut values = Vec::new();
+        let clock = problem.clock;
+
+        // We go through each scenario
+        for scenario in problem.scenarios.iter() {
+            let dir = Tito::working_dir(workspace, executable, problem.fixtures.iter().chain(scenario.fixtures.iter()))?;
+            if let (Some(interactor), Some(program)) = (interactor, problem.interactor.as_ref()) {
+                values.push(self.run_interaction(executable, (interactor, program), scenario, clock, &dir, workspace)?);
+                continue;
+            }
+
+            let final_args: Vec<_> = executable.arguments.clone().into_iter().chain(scenario.arguments.clone().unwrap_or_default()).collect();
 
-        // Now, tool execution
-        for (idx, (tool, queen)) in tools.into_iter().enumerate() {
-            let args: Vec<String> = tool.arguments.iter().map(|arg| {
-                let arg = arg.replace("{filename}", &filename.to_string_lossy());
-                arg.replace("{pwd}", &path.to_string_lossy())
-            }).collect();
-
-            let utility = tool.utility.replace("{pwd}", &path.to_string_lossy());
-
-            if queen {
-                // We go through each scenario
-                for scenario in &scenarios {
-
-                    let final_args: Vec<_> = args.clone().into_iter().chain(scenario.arguments.clone().unwrap_or_else(|| vec![]).into_iter()).collect();
-
-                    let mut child = match Command::new(&utility)
-                        .current_dir(path.clone())
-                        .args(&final_args)
-                        .stdin(Stdio::piped()) // Para poder pasar argumentos al programa
-                        .stderr(Stdio::piped()) // Para poder capturar la salida de error
-                        .stdout(Stdio::piped()).spawn() {
-                            Ok(v) => v,
-                            Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+            let mut command = Command::new(&executable.utility);
+            command.current_dir(&dir)
+                .args(&final_args)
+                .stdin(Stdio::piped()) // Para poder pasar argumentos al programa
+                .stderr(Stdio::piped()) // Para poder capturar la salida de error
+                .stdout(Stdio::piped());
+            let max_time = Tito::limit_time(&mut command, scenario, clock);
+            self.confine(&mut command, executable, &dir)?;
+            command.envs(&scenario.env);
+            // The input is read before the program starts, so that failing to read it leaves nothing running
+            let input = Tito::input(scenario)?;
+
+            let start = Instant::now();
+            let mut child = match command.spawn() {
+                Ok(v) => v,
+                Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+            };
+
+            // The input is fed while the output is read, so a program echoing a long input does not block
+            let streams = process::Streams::start(&mut child, input, scenario.max_output.unwrap_or(DEFAULT_MAX_OUTPUT), MAX_STDERR);
+            let memory = self.memory_limit(scenario.max_ram.map(u64::from));
+            let waited = match process::wait_group_timeout(&mut child, max_time, memory, streams.overflow()) {
+                Ok(Some((status, usage, leftovers))) => {
+                    Tito::report_leftovers(leftovers);
+                    Some((status, usage))
+                },
+                Ok(None) => {
+                    // child hasn't exited yet, or printed too much, so we kill it along with everything it started
+                    match process::kill_group(&mut child) {
+                        Ok(_) => (),
+                        Err(e) => {
+                            log::warn!("Could not kill process: {}", e);
+                        }
                     };
+                    None
+                },
+                Err(e) => {
+                    if let Err(e) = process::kill_group(&mut child) {
+                        log::warn!("Could not kill process: {}", e);
+                    }
+                    return Err(Error::WaitTimeoutError(format!("{}", e)));
+                }
+            };
+            let wall_time = start.elapsed();
 
-                    // We declare the child_stdin inside a block of code in case an input is mandatory
-                    if let Some(input) = &scenario.input {
-                        // Mandamos el caso como cadena al hijo
-                        let child_stdin = match child.stdin.as_mut() {
-                            Some(v) => v,
-                            None => {
-                                match child.kill() {
-                                    Ok(_) => (),
-                                    Err(e) => {
-                                        log::error!("{}", e);
-                                    }
-                                }
-                                return Err(Error::ChildStdinRef)
+            let output = streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?;
+            if output.overflow {
+                values.push(Execution{output: Err(Error::OutputExceeded), peak_ram: None, wall_time, cpu_time: None, interaction: None});
+                continue;
+            }
+            let (status, usage) = match waited {
+                Some(v) => v,
+                None => {
+                    values.push(Execution{output: Err(Error::TimeExceeded), peak_ram: None, wall_time, cpu_time: None, interaction: None});
+                    continue;
+                }
+            };
+
+            if let Some(peak_ram) = usage.peak_ram {
+                log::info!("Scenario used a peak of {} bytes of ram", peak_ram);
+            }
+            if let Some(cpu_time) = usage.cpu_time {
+                log::info!("Scenario used {:.3} s of cpu", cpu_time.as_secs_f64());
+            }
+
+            if Tito::exceeded_time(scenario, clock, &usage) {
+                values.push(Execution{output: Err(Error::TimeExceeded), peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: None});
+                continue;
+            }
+
+            if usage.out_of_memory {
+                values.push(Execution{output: Err(Error::MemoryExceeded), peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: None});
+                continue;
+            }
+
+            let output = if status.success() {
+                String::from_utf8(output.stdout).map_err(|_e| Error::Utf8)
+            } else {
+                // Only this scenario fails, the rest are still judged
+                Err(Tito::failure(executable, status, &output.stderr))
+            };
+            values.push(Execution{output, peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: None});
+        }
+        Ok(values)
+    }
+
+    /// Copies the program, embedding its source code unless it is already there
+    fn embed_program(program: &Program) -> Result<Program, Error> {
+        if program.code.is_some() {
+            return Ok(program.clone());
+        }
+        let code = std::fs::read_to_string(&program.source).map_err(Error::IOError)?;
+        Ok(Program{code: Some(code), ..program.clone()})
+    }
+
+    /// Input of the scenario, read from its file if it has one
+    fn input(scenario: &Scenario) -> Result<Option<Vec<u8>>, Error> {
+        match &scenario.input_file {
+            Some(path) => std::fs::read(path).map(Some).map_err(|e| Error::TestDataError(format!("could not read \"{}\", {}", path, e))),
+            None => Ok(scenario.input.as_ref().map(|input| input.as_bytes().to_vec()))
+        }
+    }
+
+    /// Expected output of the scenario, read from its file if it has one. Unlike inputs, outputs have to be text.
+    fn expected_output(scenario: &Scenario) -> Result<Option<String>, Error> {
+        match &scenario.output_file {
+            Some(path) => {
+                let content = std::fs::read(path).map_err(|e| Error::TestDataError(format!("could not read \"{}\", {}", path, e)))?;
+                String::from_utf8(content).map(Some).map_err(|_e| Error::TestDataError(format!("\"{}\" is not text, outputs are compared as utf-8", path)))
+            },
+            None => Ok(scenario.output.clone())
+        }
+    }
+
+    /// Copies the fixture, embedding its files, found relative to `base`
+    fn embed_fixture(fixture: &Fixture, base: &Path) -> Result<Fixture, Error> {
+        let path = base.join(&fixture.path);
+        let name = match path.file_name() {
+            Some(name) => PathBuf::from(name),
+            None => return Err(Error::InvalidFixture(format!("\"{}\" does not name a file or directory", fixture.path)))
+        };
+        let mut files = BTreeMap::new();
+        Tito::read_fixture(&path, &name, &mut files).map_err(|e| Error::InvalidFixture(format!("could not read \"{}\", {}", fixture.path, e)))?;
+        Ok(Fixture{files: Some(files), ..fixture.clone()})
+    }
+
+    /// Adds the file at `path`, or every file under it if it is a directory, to the files of a fixture as `name`
+    fn read_fixture(path: &Path, name: &Path, files: &mut BTreeMap<String, String>) -> std::io::Result<()> {
+        // Links could point back to a directory above them, so they are not followed
+        let metadata = std::fs::symlink_metadata(path)?;
+        if metadata.file_type().is_symlink() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} is a symbolic link", path.display())));
+        }
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                Tito::read_fixture(&entry.path(), &name.join(entry.file_name()), files)?;
+            }
+        } else {
+            // Paths are stored with forward slashes, so arenas work on any platform
+            let name = name.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            files.insert(name, BASE64.encode(std::fs::read(path)?));
+        }
+        Ok(())
+    }
+
+    /// Creates a fresh `run` directory in the workspace, holding a copy of what the compilation built, so that tools
+    /// like `./a.out` are found, and the files of the fixtures. The source is left out unless the program runs it.
+    fn working_dir<'a>(workspace: &Path, executable: &Executable, fixtures: impl Iterator<Item = &'a Fixture>) -> Result<PathBuf, Error> {
+        let dir = workspace.join("run");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(Error::IOError)?;
+        }
+        std::fs::create_dir_all(&dir).map_err(Error::IOError)?;
+        for item in std::fs::read_dir(&executable.dir).map_err(Error::IOError)? {
+            let item = item.map_err(Error::IOError)?;
+            if executable.source.as_deref().is_some_and(|source| item.file_name() == source) {
+                continue;
+            }
+            let target = dir.join(item.file_name());
+            if item.file_type().map_err(Error::IOError)?.is_dir() {
+                cache::copy_dir(&item.path(), &target).map_err(Error::IOError)?;
+            } else {
+                std::fs::copy(item.path(), target).map_err(Error::IOError)?;
+            }
+        }
+
+        for fixture in fixtures {
+            let files = match &fixture.files {
+                Some(files) => files,
+                None => return Err(Error::InvalidFixture(format!("\"{}\" is not embedded in the arena", fixture.path)))
+            };
+            for (name, content) in files.iter() {
+                // Arenas could come from anywhere, so files can not be written outside of the directory
+                let relative = Path::new(name);
+                if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+                    return Err(Error::InvalidFixture(format!("\"{}\" is not a path inside the working directory", name)));
+                }
+                let content = BASE64.decode(content).map_err(|e| Error::InvalidFixture(format!("the content of \"{}\" is not valid base64, {}", name, e)))?;
+                let target = dir.join(relative);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent).map_err(Error::IOError)?;
+                }
+                std::fs::write(&target, content).map_err(Error::IOError)?;
+            }
+        }
+        Ok(dir)
+    }
+
+    /// Copies the checker, embedding the source code if it is a program
+    fn embed_checker(checker: &Checker) -> Result<Checker, Error> {
+        match checker {
+            Checker::Program(program) => Ok(Checker::Program(Tito::embed_program(program)?)),
+            other => Ok(other.clone())
+        }
+    }
+
+    /// Programs of the instructor used by a problem, like checkers and interactors
+    fn programs(problem: &Problem) -> impl Iterator<Item = &Program> {
+        let checkers = problem.checker.iter().chain(problem.scenarios.iter().filter_map(|scenario| scenario.checker.as_ref()));
+        checkers.filter_map(|checker| match checker {
+            Checker::Program(program) => Some(program),
+            _ => None
+        }).chain(problem.interactor.iter())
+    }
+
+    /// Languages needed to grade the problems, including the ones of the programs of the instructor
+    fn languages<'a, T: Iterator<Item = &'a Problem>>(problems: T) -> HashSet<Language> {
+        let mut languages = HashSet::new();
+        for problem in problems {
+            languages.extend(problem.language.clone());
+            languages.extend(Tito::programs(problem).map(|program| program.language.clone()));
+        }
+        languages
+    }
+
+    /// Compiles every distinct program of the instructor, each one in its own directory
+    fn compile_programs<'a, T: Iterator<Item = &'a Program>>(&self, programs: T) -> Result<HashMap<(Language, String), Executable>, Error> {
+        let mut executables = HashMap::new();
+        for program in programs {
+            let code = match &program.code {
+                Some(code) => code.clone(),
+                None => return Err(Error::NoProgramCode(program.source.clone()))
+            };
+            let key = (program.language.clone(), code);
+            if executables.contains_key(&key) {
+                continue;
+            }
+            let executable = self.compile_program(program, &format!("program-{}", executables.len()))?;
+            executables.insert(key, executable);
+        }
+        Ok(executables)
+    }
+
+    /// Compiles a program of the instructor, whose code is embedded, in the given directory of the workspace
+    fn compile_program(&self, program: &Program, dir: &str) -> Result<Executable, Error> {
+        let code = match &program.code {
+            Some(code) => code,
+            None => return Err(Error::NoProgramCode(program.source.clone()))
+        };
+        let language_settings = match self.language_settings.get(&program.language) {
+            Some(v) => v,
+            None => return Err(Error::NoLangSettings(serde_json::to_string(&program.language).unwrap()))
+        };
+
+        log::info!("Compiling \"{}\"", program.source);
+        let mut path = PathBuf::from(self.dir.path());
+        path.push(dir);
+        std::fs::create_dir_all(&path).map_err(Error::IOError)?;
+        self.compile(code, language_settings, &path).map_err(|e| Error::JudgeFailure(format!("{}", e)))
+    }
+
+    /// Looks up the compiled version of a program
+    fn executable<'a>(executables: &'a HashMap<(Language, String), Executable>, program: &Program) -> Result<&'a Executable, Error> {
+        let key = (program.language.clone(), program.code.clone().unwrap_or_default());
+        executables.get(&key).ok_or_else(|| Error::NoProgramCode(program.source.clone()))
+    }
+
+    /// Runs a generator with the arguments of its command line, like `gen 1000 seed=7`, returning what it writes.
+    /// Outputs are stored in the cache, as generators are expected to always write the same for the same arguments.
+    fn generate(&self, command: &str, generators: &HashMap<String, Program>, executables: &HashMap<(Language, String), Executable>) -> Result<String, Error> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arguments: Vec<String> = words.map(String::from).collect();
+        let program = generators.get(name).ok_or_else(|| Error::GeneratorFailure(command.to_string(), "there is no such generator".to_string()))?;
+        let executable = Tito::executable(executables, program)?;
+
+        let cached = match (&self.cache, self.language_settings.get(&program.language)) {
+            (Some(cache), Some(language_settings)) => {
+                let key = Cache::output_key(&Cache::key(program.code.as_deref().unwrap_or_default(), language_settings), &arguments);
+                match cache.fetch_output(&key) {
+                    Ok(output) => Some((cache, key, output)),
+                    Err(e) => {
+                        log::warn!("Could not read output {} from the cache, {}", key, e);
+                        None
+                    }
+                }
+            },
+            _ => None
+        };
+        let output = match cached {
+            Some((_, key, Some(output))) => {
+                log::info!("Using cached output {}", key);
+                output
+            },
+            _ => {
+                let output = self.run_generator(command, executable, &arguments)?;
+                if let Some((cache, key, None)) = &cached {
+                    if let Err(e) = cache.store_output(key, &output) {
+                        log::warn!("Could not store output {} in the cache, {}", key, e);
+                    }
+                }
+                output
+            }
+        };
+        String::from_utf8(output).map_err(|_e| Error::GeneratorFailure(command.to_string(), "its output is not text".to_string()))
+    }
+
+    /// Runs a compiled generator in its own directory, without input
+    fn run_generator(&self, command: &str, executable: &Executable, arguments: &[String]) -> Result<Vec<u8>, Error> {
+        let mut child_command = Command::new(&executable.utility);
+        child_command.current_dir(&executable.dir)
+            .args(&executable.arguments)
+            .args(arguments)
+            .env_clear()
+            .envs(&executable.env)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped());
+        process::new_group(&mut child_command);
+        let mut child = match child_command.spawn() {
+            Ok(v) => v,
+            Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+        };
+
+        let streams = process::Streams::start(&mut child, None, DEFAULT_MAX_OUTPUT, MAX_STDERR);
+        let waited = match process::wait_group_timeout(&mut child, GENERATOR_TIMEOUT, None, streams.overflow()) {
+            Ok(waited) => waited,
+            Err(e) => {
+                if let Err(e) = process::kill_group(&mut child) {
+                    log::warn!("Could not kill process: {}", e);
+                }
+                return Err(Error::WaitTimeoutError(format!("{}", e)));
+            }
+        };
+        if waited.is_none() {
+            if let Err(e) = process::kill_group(&mut child) {
+                log::warn!("Could not kill process: {}", e);
+            }
+        }
+        let output = streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?;
+        let status = match waited {
+            _ if output.overflow => return Err(Error::GeneratorFailure(command.to_string(), format!("it wrote more than {} bytes", DEFAULT_MAX_OUTPUT))),
+            Some((status, _usage, _leftovers)) => status,
+            None => return Err(Error::GeneratorFailure(command.to_string(), format!("it exceeded the maximum time of {} s", GENERATOR_TIMEOUT.as_secs())))
+        };
+        if !status.success() {
+            return Err(Error::GeneratorFailure(command.to_string(), Tito::failure(executable, status, &output.stderr).to_string()));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Decides over the output of a scenario, with a built-in comparison or a checker program
+    fn judge(&self, checker: Option<&Checker>, executables: &HashMap<(Language, String), Executable>, scenario: &Scenario, candidate: &str, expected: &str, workspace: &Path) -> Judgement {
+        match checker {
+            Some(Checker::Program(program)) => {
+                match Tito::executable(executables, program).and_then(|executable| self.run_checker(executable, program, scenario, candidate, expected, workspace)) {
+                    Ok(judgement) => judgement,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        Judgement{verdict: Verdict::CheckerFailure, score: 0.0, message: None}
+                    }
+                }
+            },
+            other => if other.unwrap_or(&Checker::Exact).check(candidate, expected) {
+                Judgement{verdict: Verdict::Accepted, score: 1.0, message: None}
+            } else {
+                Judgement{verdict: Verdict::WrongAnswer, score: 0.0, message: None}
+            }
+        }
+    }
+
+    /// Runs a checker program, giving it the files of the scenario and interpreting how it ends as its protocol says
+    fn run_checker(&self, checker: &Executable, program: &Program, scenario: &Scenario, candidate: &str, expected: &str, workspace: &Path) -> Result<Judgement, Error> {
+        let mut path = workspace.to_path_buf();
+        path.push("judge");
+        std::fs::create_dir_all(&path).map_err(Error::IOError)?;
+
+        let mut files = Vec::new();
+        let input = Tito::input(scenario)?.unwrap_or_default();
+        for (name, content) in [("input.txt", input.as_slice()), ("output.txt", candidate.as_bytes()), ("answer.txt", expected.as_bytes())] {
+            let mut filename = path.clone();
+            filename.push(name);
+            std::fs::write(&filename, content).map_err(Error::IOError)?;
+            files.push(filename);
+        }
+        let feedback = Tito::feedback_dir(&path)?;
+
+        let mut command = Command::new(&checker.utility);
+        command.current_dir(Tito::private_dir(workspace, checker)?).args(&checker.arguments).env_clear().envs(&checker.env);
+        match program.protocol {
+            Protocol::Testlib => command.args(&files).stdin(Stdio::null()),
+            Protocol::Kattis => command.arg(&files[0]).arg(&files[2]).arg(&feedback).stdin(File::open(&files[1]).map_err(Error::IOError)?)
+        };
+        let mut child = match command
+            .args(&program.arguments)
+            .stderr(Stdio::piped())
+            .stdout(Stdio::null()).spawn() {
+                Ok(v) => v,
+                Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+        };
+        let streams = process::Streams::start(&mut child, None, 0, MAX_STDERR);
+        let status = match process::wait_timeout(&mut child, CHECKER_TIMEOUT) {
+            Ok(Some((status, _usage))) => status,
+            Ok(None) => {
+                if let Err(e) = child.kill().and_then(|_| child.wait()) {
+                    log::warn!("Could not kill process: {}", e);
+                }
+                return Err(Error::JudgeFailure("the checker exceeded the maximum time".to_string()));
+            },
+            Err(e) => return Err(Error::WaitTimeoutError(format!("{}", e)))
+        };
+        let output = streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?;
+        Tito::judgement(program.protocol, status, &output.stderr, &feedback)
+    }
+
+    /// Copy of the directory of a program of the instructor in the workspace, made once per job, so that programs
+    /// writing files next to them do not run into the ones of other workers
+    fn private_dir(workspace: &Path, executable: &Executable) -> Result<PathBuf, Error> {
+        let mut dir = workspace.join("programs");
+        dir.push(executable.dir.file_name().unwrap_or_default());
+        if !dir.exists() {
+            cache::copy_dir(&executable.dir, &dir).map_err(Error::IOError)?;
+        }
+        Ok(dir)
+    }
+
+    /// Empties the directory where Kattis validators leave their messages
+    fn feedback_dir(path: &Path) -> Result<PathBuf, Error> {
+        let mut feedback = path.to_path_buf();
+        feedback.push("feedback");
+        if feedback.exists() {
+            std::fs::remove_dir_all(&feedback).map_err(Error::IOError)?;
+        }
+        std::fs::create_dir_all(&feedback).map_err(Error::IOError)?;
+        Ok(feedback)
+    }
+
+    /// Interprets how a checker or interactor ended, according to its protocol
+    fn judgement(protocol: Protocol, status: ExitStatus, stderr: &[u8], feedback: &Path) -> Result<Judgement, Error> {
+        match protocol {
+            Protocol::Testlib => Tito::testlib_judgement(status, stderr),
+            Protocol::Kattis => Tito::kattis_judgement(status, feedback)
+        }
+    }
+
+    /// Interprets the exit code and stderr of a checker or interactor as testlib does
+    fn testlib_judgement(status: ExitStatus, stderr: &[u8]) -> Result<Judgement, Error> {
+        let mut message = String::from_utf8_lossy(stderr).trim().to_string();
+        truncate(&mut message, MAX_CHECKER_MESSAGE);
+
+        let (verdict, score) = match status.code() {
+            Some(0) => (Verdict::Accepted, 1.0),
+            // Wrong answer, presentation error, `_dirt` and `_unexpected_eof`, which is a truncated output
+            Some(1) | Some(2) | Some(4) | Some(8) => (Verdict::WrongAnswer, 0.0),
+            // testlib's `quitp` reports "points <value> <message>", the value being the fraction of points earned
+            Some(7) => match message.strip_prefix("points ").and_then(|rest| rest.split_whitespace().next()).and_then(|value| value.parse::<f64>().ok()) {
+                Some(score) if score >= 1.0 => (Verdict::Accepted, 1.0),
+                Some(score) if score > 0.0 => (Verdict::Partial, score),
+                Some(_) => (Verdict::WrongAnswer, 0.0),
+                None => return Err(Error::JudgeFailure(format!("could not read points from \"{}\"", message)))
+            },
+            // testlib's `_pc(x)` ends with `50 + x`, x being the percentage of points earned
+            Some(code) if code >= TESTLIB_PC_BASE => match f64::from(code - TESTLIB_PC_BASE) / 100.0 {
+                score if score >= 1.0 => (Verdict::Accepted, 1.0),
+                score if score > 0.0 => (Verdict::Partial, score),
+                _ => (Verdict::WrongAnswer, 0.0)
+            },
+            _ => return Err(Error::JudgeFailure(format!("the judging program failed with {}, {}", status, message)))
+        };
+        Ok(Judgement{verdict, score, message: if message.is_empty() { None } else { Some(message) }})
+    }
+
+    /// Interprets the exit code of a Kattis validator, taking the message from its feedback directory
+    fn kattis_judgement(status: ExitStatus, feedback: &Path) -> Result<Judgement, Error> {
+        // The message meant for the competitor is preferred over the one meant for the judges
+        let mut message = ["teammessage.txt", "judgemessage.txt"].iter()
+            .filter_map(|name| std::fs::read(feedback.join(name)).ok())
+            .map(|content| String::from_utf8_lossy(&content).trim().to_string())
+            .find(|message| !message.is_empty())
+            .unwrap_or_default();
+        truncate(&mut message, MAX_CHECKER_MESSAGE);
+
+        let (verdict, score) = match status.code() {
+            Some(42) => (Verdict::Accepted, 1.0),
+            Some(43) => (Verdict::WrongAnswer, 0.0),
+            _ => return Err(Error::JudgeFailure(format!("the validator failed with {}, {}", status, message)))
+        };
+        Ok(Judgement{verdict, score, message: if message.is_empty() { None } else { Some(message) }})
+    }
+
+    /// Runs the program of a scenario against the interactor, connecting the output of each one to the input of the
+    /// other. The interactor is called as `interactor <input> <output>`, or as a Kattis validator, and decides the verdict
+    /// as a checker would.
+    fn run_interaction(&self, executable: &Executable, (interactor, program): (&Executable, &Program), scenario: &Scenario, clock: Clock, dir: &Path, workspace: &Path) -> Result<Execution, Error> {
+        let mut path = workspace.to_path_buf();
+        path.push("judge");
+        std::fs::create_dir_all(&path).map_err(Error::IOError)?;
+        let mut input = path.clone();
+        input.push("input.txt");
+        std::fs::write(&input, Tito::input(scenario)?.unwrap_or_default()).map_err(Error::IOError)?;
+        let mut output = path.clone();
+        output.push("output.txt");
+        if output.exists() {
+            std::fs::remove_file(&output).map_err(Error::IOError)?;
+        }
+        let mut answer = path.clone();
+        answer.push("answer.txt");
+        std::fs::write(&answer, Tito::expected_output(scenario)?.unwrap_or_default()).map_err(Error::IOError)?;
+        let feedback = Tito::feedback_dir(&path)?;
+
+        let mut command = Command::new(&executable.utility);
+        command.current_dir(dir)
+            .args(&executable.arguments)
+            .args(scenario.arguments.clone().unwrap_or_default())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped());
+        let max_time = Tito::limit_time(&mut command, scenario, clock);
+        self.confine(&mut command, executable, dir)?;
+        command.envs(&scenario.env);
+        // The interactor leads a group of its own too, so that whatever it starts is killed along with it
+        let mut judge_command = Command::new(&interactor.utility);
+        judge_command.current_dir(Tito::private_dir(workspace, interactor)?).args(&interactor.arguments).env_clear().envs(&interactor.env);
+        match program.protocol {
+            Protocol::Testlib => judge_command.arg(&input).arg(&output),
+            Protocol::Kattis => judge_command.arg(&input).arg(&answer).arg(&feedback)
+        };
+        process::new_group(&mut judge_command);
+
+        let start = Instant::now();
+        let mut child = match command.spawn() {
+            Ok(v) => v,
+            Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
+        };
+        let (child_stdin, child_stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                Tito::stop(&mut vec![child]);
+                return Err(Error::ChildStdinRef);
+            }
+        };
+        let mut judge = match judge_command
+            .args(&program.arguments)
+            .stdin(Stdio::from(child_stdout))
+            .stdout(Stdio::from(child_stdin))
+            .stderr(Stdio::piped()).spawn() {
+                Ok(v) => v,
+                Err(e) => {
+                    Tito::stop(&mut vec![child]);
+                    return Err(Error::ChildProcessError(format!("{}", e)));
+                }
+        };
+        // The command holds the ends of the pipes between both programs, which would never see the other one leave
+        drop(judge_command);
+
+        let streams = process::Streams::start(&mut child, None, 0, MAX_STDERR);
+        let judge_streams = process::Streams::start(&mut judge, None, 0, MAX_STDERR);
+
+        // Both processes share the time limit of the scenario, and both are stopped when the program can not go on
+        let (status, usage) = match process::wait_group_timeout(&mut child, max_time, self.memory_limit(scenario.max_ram.map(u64::from)), streams.overflow()) {
+            Ok(Some((status, usage, leftovers))) => {
+                Tito::report_leftovers(leftovers);
+                (status, usage)
+            },
+            Ok(None) => {
+                Tito::stop(&mut vec![child, judge]);
+                return Ok(Execution{output: Err(Error::TimeExceeded), peak_ram: None, wall_time: start.elapsed(), cpu_time: None, interaction: None});
+            },
+            Err(e) => {
+                Tito::stop(&mut vec![child, judge]);
+                return Err(Error::WaitTimeoutError(format!("{}", e)));
+            }
+        };
+        let wall_time = start.elapsed();
+
+        let remaining = max_time.saturating_sub(wall_time).max(INTERACTOR_GRACE);
+        let interaction = match process::wait_group_timeout(&mut judge, remaining, None, &process::Alarm::new()) {
+            Ok(Some((judge_status, _usage, _leftovers))) => {
+                let output = judge_streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?;
+                Tito::judgement(program.protocol, judge_status, &output.stderr, &feedback)
+            },
+            Ok(None) => {
+                Tito::stop(&mut vec![judge]);
+                Err(Error::JudgeFailure("the interactor exceeded the maximum time".to_string()))
+            },
+            Err(e) => {
+                Tito::stop(&mut vec![judge]);
+                return Err(Error::WaitTimeoutError(format!("{}", e)));
+            }
+        };
+        let interaction = interaction.unwrap_or_else(|e| {
+            log::error!("{}", e);
+            Judgement{verdict: Verdict::CheckerFailure, score: 0.0, message: None}
+        });
+
+        let stderr = streams.finish().map_err(|e| Error::WaitOutputError(format!("{}", e)))?.stderr;
+        if Tito::exceeded_time(scenario, clock, &usage) {
+            return Ok(Execution{output: Err(Error::TimeExceeded), peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: Some(interaction)});
+        }
+        if usage.out_of_memory {
+            return Ok(Execution{output: Err(Error::MemoryExceeded), peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: Some(interaction)});
+        }
+
+        // What the interactor leaves in its output file plays the role of the output of the program
+        let output = if status.success() {
+            std::fs::read(&output).or_else(|_| Ok(Vec::new())).and_then(|content| String::from_utf8(content).map_err(|_e| Error::Utf8))
+        } else {
+            Err(Tito::failure(executable, status, &stderr))
+        };
+        Ok(Execution{output, peak_ram: usage.peak_ram, wall_time, cpu_time: usage.cpu_time, interaction: Some(interaction)})
+    }
+
+    /// Plays a match between the bots, in the order of their seats, through the referee. The referee is called as
+    /// `referee <players>` and sends `<seat> <message>` lines to talk to a bot, or `#` lines that only go to the log. It receives every line a bot prints as
+    /// `<seat> <line>`, plus `terminated <seat>` when a bot stops printing and `timeout <seat>` when a bot takes too
+    /// long to answer. The match ends when the referee prints `result <score>...`, with a score per seat.
+    fn play_match(&self, referee: &Executable, bots: &[&Executable], tournament: &Tournament, transcript: &mut Transcript) -> Result<Vec<f64>, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let mut children = Vec::new();
+        let mut inputs = Vec::new();
+        for (seat, bot) in bots.iter().enumerate() {
+            // Every match starts from a fresh copy of the bot, so nothing it writes carries over to the next one
+            let dir = match Tito::working_dir(&self.dir.path().join(format!("seat-{}", seat)), bot, std::iter::empty()) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    Tito::stop(&mut children);
+                    return Err(e);
+                }
+            };
+            let mut command = Command::new(&bot.utility);
+            command.current_dir(&dir)
+                .args(&bot.arguments)
+                .stdin(Stdio::piped())
+                .stderr(Stdio::piped())
+                .stdout(Stdio::piped());
+            if let Err(e) = self.confine(&mut command, bot, &dir) {
+                Tito::stop(&mut children);
+                return Err(e);
+            }
+            let mut child = match command.spawn() {
+                Ok(v) => v,
+                Err(e) => {
+                    Tito::stop(&mut children);
+                    return Err(Error::ChildProcessError(format!("{}", e)));
+                }
+            };
+            inputs.push(child.stdin.take());
+            Tito::forward_lines(child.stdout.take(), sender.clone(), move |line| MatchEvent::Bot(seat, line));
+            Tito::forward_lines(child.stderr.take(), sender.clone(), move |line| MatchEvent::Stderr(Some(seat), line));
+            children.push(child);
+        }
+
+        let mut command = Command::new(&referee.utility);
+        command.current_dir(&referee.dir)
+            .args(&referee.arguments)
+            .env_clear()
+            .envs(&referee.env)
+            .arg(bots.len().to_string())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped());
+        process::new_group(&mut command);
+        let mut judge = match command.spawn() {
+                Ok(v) => v,
+                Err(e) => {
+                    Tito::stop(&mut children);
+                    return Err(Error::ChildProcessError(format!("{}", e)));
+                }
+        };
+        let mut judge_input = judge.stdin.take();
+        Tito::forward_lines(judge.stdout.take(), sender.clone(), MatchEvent::Referee);
+        Tito::forward_lines(judge.stderr.take(), sender, |line| MatchEvent::Stderr(None, line));
+        children.push(judge);
+
+        let deadline = Instant::now() + Duration::from_secs_f32(tournament.max_time);
+        let move_time = tournament.move_time.map(Duration::from_secs_f32);
+        // Moment since which each bot owes an answer to the referee
+        let mut waiting: Vec<Option<Instant>> = vec![None; bots.len()];
+        // Bots are measured all along the match, and the ones going over the memory limit are killed
+        let memory = self.memory_limit(tournament.max_ram.map(u64::from));
+        let mut measured = Instant::now();
+        let mut over_memory = vec![false; bots.len()];
+        let result = loop {
+            let now = Instant::now();
+            if now >= deadline {
+                transcript.note("the match exceeded the maximum time".to_string());
+                break Err(Error::MatchFailure("the match exceeded the maximum time".to_string()));
+            }
+            if let Some(memory) = memory.filter(|_| now >= measured + process::MEMORY_INTERVAL) {
+                measured = now;
+                for (seat, over) in over_memory.iter_mut().enumerate() {
+                    if !*over && process::group_memory(&children[seat], memory.sandboxed).map(|used| used > memory.max_ram).unwrap_or(false) {
+                        *over = true;
+                        transcript.note(format!("{} exceeded the maximum ram", seat));
+                        if let Err(e) = process::kill_group_unreaped(&mut children[seat]) {
+                            log::warn!("Could not kill process: {}", e);
+                        }
+                    }
+                }
+            }
+            let mut wake = waiting.iter().flatten().filter_map(|since| move_time.map(|move_time| *since + move_time)).fold(deadline, Instant::min);
+            if memory.is_some() {
+                wake = wake.min(measured + process::MEMORY_INTERVAL);
+            }
+
+            match receiver.recv_timeout(wake.saturating_duration_since(now)) {
+                Ok(MatchEvent::Referee(Some(line))) => {
+                    let (head, rest) = line.split_once(' ').unwrap_or((&line, ""));
+                    if head == "result" {
+                        transcript.note(format!("referee: {}", line));
+                        let scores: Result<Vec<f64>, _> = rest.split_whitespace().map(|score| score.parse::<f64>()).collect();
+                        match scores {
+                            Ok(scores) if scores.len() == bots.len() && scores.iter().all(|score| score.is_finite()) => break Ok(scores),
+                            _ => break Err(Error::MatchFailure(format!("invalid result \"{}\"", line)))
+                        }
+                    }
+                    match head.parse::<usize>() {
+                        // Comments only go to the log
+                        _ if head.starts_with('#') => transcript.note(format!("referee: {}", line)),
+                        Ok(seat) if seat < bots.len() => {
+                            transcript.note_for(Audience::Seat(seat), format!("referee > {}: {}", seat, rest));
+                            if !Tito::tell(&mut inputs[seat], rest) {
+                                transcript.note(format!("{} does not read anymore", seat));
                             }
-                        };
-                        match child_stdin.write_all(input.as_bytes()) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                log::error!("{}", e);
-                                match child.kill() {
-                                    Ok(_) => (),
-                                    Err(e) => {
-                                        log::error!("{}", e);
-                                    }
-                                }
-                                return Err(Error::ChildStdinFeed);
+                            if waiting[seat].is_none() {
+                                waiting[seat] = Some(now);
                             }
+                        },
+                        _ => {
+                            transcript.note_for(Audience::Organizer, format!("referee: {}", line));
+                            break Err(Error::MatchFailure(format!("the referee sent \"{}\"", line)));
                         }
                     }
-            
-                    match child.wait_timeout(std::time::Duration::from_millis((scenario.max_time * 1000.0) as u64)) {
-                        Ok(v) => match v{
-                            Some(status) => status.code(),
-                            None => {
-                                // child hasn't exited yet, so we kill it
-                                match child.kill() {
-                                    Ok(_) => (),
-                                    Err(e) => {
-                                        log::warn!("Could not kill process: {}", e);
-                                    }
-                                };
-                                values.push(Err(Error::TimeExceeded));
-                                continue;
+                },
+                Ok(MatchEvent::Referee(None)) | Err(RecvTimeoutError::Disconnected) => {
+                    break Err(Error::MatchFailure("the referee ended without a result".to_string()));
+                },
+                Ok(MatchEvent::Bot(seat, Some(line))) => {
+                    transcript.note_for(Audience::Seat(seat), format!("{} > referee: {}", seat, line));
+                    waiting[seat] = None;
+                    Tito::tell(&mut judge_input, &format!("{} {}", seat, line));
+                },
+                Ok(MatchEvent::Bot(seat, None)) => {
+                    transcript.note(format!("{} terminated", seat));
+                    waiting[seat] = None;
+                    Tito::tell(&mut judge_input, &format!("terminated {}", seat));
+                },
+                Ok(MatchEvent::Stderr(Some(seat), Some(line))) => transcript.note_for(Audience::Seat(seat), format!("{} ! {}", seat, line)),
+                Ok(MatchEvent::Stderr(None, Some(line))) => transcript.note_for(Audience::Organizer, format!("referee ! {}", line)),
+                Ok(MatchEvent::Stderr(_, None)) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    for (seat, since) in waiting.iter_mut().enumerate() {
+                        if let (Some(started), Some(move_time)) = (*since, move_time) {
+                            if started.elapsed() >= move_time {
+                                transcript.note(format!("{} timed out", seat));
+                                *since = None;
+                                Tito::tell(&mut judge_input, &format!("timeout {}", seat));
                             }
-                        },
-                        Err(e) => return Err(Error::WaitTimeoutError(format!("{}", e)))
-                    };
-            
-                    let output = match child.wait_with_output() {
-                        Ok(v) => v,
-                        Err(e) => return Err(Error::WaitOutputError(format!("{}", e)))
-                    };
-            
-                    if output.status.success() {
-                        values.push(String::from_utf8(output.stdout).map_err(|_e| Error::Utf8));
-                    } else {
-                        return Err(Error::RuntimeError(String::from_utf8_lossy(&output.stderr).to_string()));
+                        }
                     }
                 }
-            } else {
-                // We just execute carelessly
-                let output = match Command::new(utility)
-                    .current_dir(path.clone())
-                    .args(&args)
-                    .stdin(Stdio::null()) // Para poder pasar argumentos al programa
-                    .stderr(Stdio::piped()) // Para poder capturar la salida de error
-                    .stdout(Stdio::null()).output() {
-                        Ok(v) => v,
-                        Err(e) => return Err(Error::ChildProcessError(format!("{}", e)))
-                };
-                if !output.status.success() {
-                    return Err(Error::ToolFailure(idx, String::from_utf8_lossy(&output.stderr).to_string()));
+            }
+        };
+
+        Tito::stop(&mut children);
+        result
+    }
+
+    /// Sends every line read from the pipe through the channel, and `None` once it closes
+    fn forward_lines<R, F>(pipe: Option<R>, sender: Sender<MatchEvent>, event: F)
+    where R: Read + Send + 'static, F: Fn(Option<String>) -> MatchEvent + Send + 'static {
+        let mut pipe = match pipe {
+            Some(pipe) => BufReader::new(pipe),
+            None => return
+        };
+        std::thread::spawn(move || {
+            let mut line = Vec::new();
+            while let Ok(read) = pipe.read_until(b'\n', &mut line) {
+                if read == 0 {
+                    break;
                 }
-            };
+                let text = String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']).to_string();
+                if sender.send(event(Some(text))).is_err() {
+                    return;
+                }
+                line.clear();
+            }
+            // The match might be over already, in which case nobody listens
+            let _ = sender.send(event(None));
+        });
+    }
+
+    /// Writes a line to the input of a process, forgetting the input if the process does not read anymore
+    fn tell(input: &mut Option<ChildStdin>, message: &str) -> bool {
+        let written = match input {
+            Some(pipe) => pipe.write_all(format!("{}\n", message).as_bytes()).and_then(|_| pipe.flush()).is_ok(),
+            None => false
+        };
+        if !written {
+            *input = None;
         }
-        Ok(values)
+        written
+    }
+
+    /// Kills and reaps the processes, which might have ended already, along with everything they started
+    fn stop(children: &mut Vec<Child>) {
+        for mut child in children.drain(..) {
+            if let Err(e) = process::kill_group(&mut child) {
+                log::warn!("Could not kill process: {}", e);
+            }
+        }
+    }
+
+    /// Builds the environment of the programs of the competitors: the fixed variables, then the ones inherited from
+    /// the grader, and the ones of the language last
+    fn environment(language_settings: &LanguageSettings, expand: impl Fn(&str) -> String) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = FIXED_ENV.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        for name in language_settings.inherit_env.iter() {
+            if let Some(value) = std::env::var_os(name) {
+                env.insert(name.clone(), value.to_string_lossy().into_owned());
+            }
+        }
+        for (name, value) in language_settings.env.iter() {
+            env.insert(name.clone(), expand(value));
+        }
+        env
+    }
+
+    /// Limits the cpu time of the program of a scenario if the clock requires it, returning for how long, in wall
+    /// time, it can run before it is stopped
+    fn limit_time(command: &mut Command, scenario: &Scenario, clock: Clock) -> Duration {
+        let max_time = Duration::from_millis((scenario.max_time * 1000.0) as u64);
+        match clock {
+            Clock::Wall => max_time,
+            Clock::Cpu | Clock::Both => {
+                // The exact limit is checked once the program ends, this one only stops programs that spin forever
+                process::limit_cpu(command, scenario.max_time.ceil() as u64 + 1);
+                if clock == Clock::Cpu { max_time * CPU_WALL_FACTOR } else { max_time }
+            }
+        }
+    }
+
+    /// Indicates if a program that ended in time, as far as the wall clock is concerned, used too much cpu time
+    fn exceeded_time(scenario: &Scenario, clock: Clock, usage: &process::Usage) -> bool {
+        clock != Clock::Wall && usage.cpu_time.map(|cpu_time| cpu_time.as_secs_f32() > scenario.max_time).unwrap_or(false)
     }
+
+    /// Warns about processes that a program of a competitor left running after it ended, which were killed
+    fn report_leftovers(leftovers: bool) {
+        if leftovers {
+            log::warn!("The program left processes running after it ended, they were killed");
+        }
+    }
+}
+
+/// Truncates the text to at most `max` bytes, respecting char boundaries. Returns true if anything was removed.
+fn truncate(text: &mut String, max: usize) -> bool {
+    if text.len() <= max {
+        return false;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     MissingTool(String),
+    UnknownLanguage(String),
+    InvalidLanguage(String, String),
     ToolLookup(String),
     NoFileName(String),
     NoLangSettings(String),
     ChildProcessError(String),
     ToolFailure(usize, String),
     ChildStdinRef,
-    ChildStdinFeed,
     WaitOutputError(String),
     WaitTimeoutError(String),
-    RuntimeError(String),
+    RuntimeError {
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        stderr: String
+    },
     TimeExceeded,
+    MemoryExceeded,
+    OutputExceeded,
+    SecurityViolation,
     NoFileFound,
     Utf8,
     NoSolution(String, usize),
+    InvalidChecker(String, String),
+    SolutionRejected(usize, String),
+    NoProgramCode(String),
+    JudgeFailure(String),
+    InvalidTournament(String),
+    MatchFailure(String),
+    SandboxFailure(String),
+    InvalidFixture(String),
+    TestDataError(String),
+    GeneratorFailure(String, String),
     SettingsError,
     IOError(std::io::Error)
 }
@@ -403,25 +1829,316 @@ impl std::fmt::Display for Error {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         let content = match self {
             Error::MissingTool(tool) => format!("The tool \"{}\" was not found", tool),
+            Error::UnknownLanguage(language) => format!("Language \"{}\" is not a preset and has no settings", language),
+            Error::InvalidLanguage(language, detail) => format!("Settings for language \"{}\" are invalid, {}", language, detail),
             Error::ToolLookup(detail) => format!("Could not perform tool search, {}", detail),
             Error::NoFileName(problem) => format!("Problem \"{}\" did not contain a valid filename", problem),
             Error::NoLangSettings(detail) => format!("No language settings were found for language {}", detail),
             Error::ChildProcessError(detail) => format!("Could not spawn child process, {}", detail),
             Error::ToolFailure(idx, detail) => format!("Tool {} failed with the following stderr: {}", idx, detail),
-            Error::ChildStdinRef => format!("Could not obtain reference to child stdin"),
-            Error::ChildStdinFeed => format!("Could not feed input to child process"),
+            Error::ChildStdinRef => "Could not obtain reference to child stdin".to_string(),
             Error::WaitTimeoutError(detail) => format!("Wait timeout command failed, {}", detail),
             Error::WaitOutputError(detail) => format!("Wait for output failed, {}", detail),
-            Error::RuntimeError(detail) => format!("Runtime error, {}", detail),
-            Error::TimeExceeded => format!("The execution exceeded the maximum time"),
-            Error::NoFileFound => format!("Could not evaluate due to lack of file"),
-            Error::Utf8 => format!("A byte stream received was not utf-8 valid"),
+            Error::RuntimeError{stderr, ..} => format!("Runtime error, {}", stderr),
+            Error::TimeExceeded => "The execution exceeded the maximum time".to_string(),
+            Error::MemoryExceeded => "The execution exceeded the maximum ram".to_string(),
+            Error::OutputExceeded => "The execution exceeded the maximum output".to_string(),
+            Error::SecurityViolation => "The execution called a syscall denied by its seccomp profile".to_string(),
+            Error::NoFileFound => "Could not evaluate due to lack of file".to_string(),
+            Error::Utf8 => "A byte stream received was not utf-8 valid".to_string(),
             Error::NoSolution(name, idx) => format!("No solution was provided for problem {}, scenario {}", name, idx),
-            Error::SettingsError => format!("An error with the settings has occured"),
+            Error::InvalidChecker(name, detail) => format!("Checker of problem \"{}\" is invalid, {}", name, detail),
+            Error::SolutionRejected(idx, detail) => format!("The interactor rejected the solution in scenario {}, {}", idx, detail),
+            Error::NoProgramCode(source) => format!("The code of program \"{}\" is not in the arena", source),
+            Error::JudgeFailure(detail) => format!("A checker or interactor failed, {}", detail),
+            Error::InvalidTournament(detail) => format!("The tournament can not be played, {}", detail),
+            Error::MatchFailure(detail) => format!("The match could not be played, {}", detail),
+            Error::SandboxFailure(detail) => format!("Could not run the program in a sandbox, {}", detail),
+            Error::InvalidFixture(detail) => format!("Invalid fixture, {}", detail),
+            Error::TestDataError(detail) => format!("Invalid test data, {}", detail),
+            Error::GeneratorFailure(command, detail) => format!("The generator \"{}\" failed, {}", command, detail),
+            Error::SettingsError => "An error with the settings has occured".to_string(),
             Error::IOError(e) => format!("An io error occured, {}", e)
         };
         write!(formatter, "{}", content)
     }
 }
 
-impl std::error::Error for Error {}
\ No newline at end of file
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use crate::Tool;
+
+    fn testlib(code: i32, stderr: &str) -> Result<Judgement, Error> {
+        Tito::testlib_judgement(ExitStatus::from_raw(code << 8), stderr.as_bytes())
+    }
+
+    #[test]
+    fn testlib_exit_codes() {
+        for code in 0..=255 {
+            let judgement = testlib(code, "points 0.25 almost");
+            match code {
+                0 => assert_eq!(judgement.unwrap().verdict, Verdict::Accepted),
+                1 | 2 | 4 | 8 | 50 => assert_eq!(judgement.unwrap().verdict, Verdict::WrongAnswer),
+                7 => {
+                    let judgement = judgement.unwrap();
+                    assert_eq!((judgement.verdict, judgement.score), (Verdict::Partial, 0.25));
+                },
+                51..=149 => {
+                    let judgement = judgement.unwrap();
+                    assert_eq!((judgement.verdict, judgement.score), (Verdict::Partial, f64::from(code - 50) / 100.0));
+                },
+                150..=255 => {
+                    let judgement = judgement.unwrap();
+                    assert_eq!((judgement.verdict, judgement.score), (Verdict::Accepted, 1.0));
+                },
+                _ => assert!(matches!(judgement, Err(Error::JudgeFailure(_))), "exit code {}", code)
+            }
+        }
+    }
+
+    #[test]
+    fn testlib_points() {
+        assert_eq!(testlib(7, "points 1.5").unwrap().verdict, Verdict::Accepted);
+        assert_eq!(testlib(7, "points 0").unwrap().verdict, Verdict::WrongAnswer);
+        assert!(matches!(testlib(7, "no points"), Err(Error::JudgeFailure(_))));
+    }
+
+    /// Runs the script with the arguments of the program after `$0`, writing the id of every process it starts in `pids`
+    fn script(dir: &Path, script: &str) -> Executable {
+        Executable {
+            utility: "sh".to_string(),
+            arguments: vec!["-c".to_string(), format!("echo $$ >> {0}/pids; {1}", dir.display(), script), "sh".to_string()],
+            dir: dir.to_path_buf(),
+            source: None,
+            seccomp: None,
+            env: HashMap::new()
+        }
+    }
+
+    /// Indicates if any process written in `pids` is still running, giving the kernel a moment to deliver the kills
+    fn running(dir: &Path) -> bool {
+        let pids = std::fs::read_to_string(dir.join("pids")).unwrap_or_default();
+        let alive = || pids.split_whitespace().any(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+            stat.rsplit_once(')').map(|(_, rest)| !rest.trim_start().starts_with('Z')).unwrap_or(false)
+        });
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while alive() {
+            if Instant::now() > deadline {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn interactions_stop_both_programs() {
+        let dir = TempDir::new("interaction").unwrap();
+        let workspace = dir.path().join("workspace");
+        std::fs::create_dir(&workspace).unwrap();
+        let scenario: Scenario = serde_json::from_str(r#"{"input": "", "output": "", "max_time": 0.2, "points": 1}"#).unwrap();
+        let program: Program = serde_json::from_str(r#"{"source": "interactor.sh", "language": "Bash"}"#).unwrap();
+        let tito = Tito::new().unwrap();
+        let background = "sleep 30 & echo $! >> {}/pids; sleep 30";
+
+        // A program that takes too long is stopped along with the interactor
+        let (program_dir, interactor_dir) = (dir.path().join("program"), dir.path().join("interactor"));
+        for dir in [&program_dir, &interactor_dir] {
+            std::fs::create_dir(dir).unwrap();
+        }
+        let start = Instant::now();
+        let execution = tito.run_interaction(
+            &script(&program_dir, &background.replace("{}", &program_dir.to_string_lossy())),
+            (&script(&interactor_dir, &background.replace("{}", &interactor_dir.to_string_lossy())), &program),
+            &scenario, Clock::Wall, &program_dir, &workspace
+        ).unwrap();
+        assert!(matches!(execution.output, Err(Error::TimeExceeded)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!running(&program_dir) && !running(&interactor_dir));
+
+        // An interactor that outlives the program is stopped once its time is over
+        let (program_dir, interactor_dir) = (dir.path().join("quick"), dir.path().join("stuck"));
+        for dir in [&program_dir, &interactor_dir] {
+            std::fs::create_dir(dir).unwrap();
+        }
+        let start = Instant::now();
+        let execution = tito.run_interaction(
+            &script(&program_dir, "exit 0"),
+            (&script(&interactor_dir, &background.replace("{}", &interactor_dir.to_string_lossy())), &program),
+            &scenario, Clock::Wall, &program_dir, &workspace
+        ).unwrap();
+        assert_eq!(execution.interaction.unwrap().verdict, Verdict::CheckerFailure);
+        assert!(start.elapsed() < INTERACTOR_GRACE + Duration::from_secs(3));
+        assert!(!running(&interactor_dir));
+    }
+
+    #[test]
+    fn working_dirs_hold_the_build_and_the_fixtures() {
+        let dir = TempDir::new("working").unwrap();
+        let build = dir.path().join("build");
+        std::fs::create_dir_all(build.join("classes")).unwrap();
+        for file in ["source.cpp", "a.out", "classes/Main.class"] {
+            std::fs::write(build.join(file), file).unwrap();
+        }
+        let mut executable = script(&build, "");
+        executable.source = Some("source.cpp".to_string());
+        let fixture = |name: &str| Fixture{path: name.to_string(), files: Some(BTreeMap::from([(name.to_string(), BASE64.encode(name))]))};
+
+        let run = Tito::working_dir(dir.path(), &executable, [fixture("data/input.txt")].iter()).unwrap();
+        assert!(!run.join("source.cpp").exists());
+        assert_eq!(std::fs::read_to_string(run.join("a.out")).unwrap(), "a.out");
+        assert_eq!(std::fs::read_to_string(run.join("classes/Main.class")).unwrap(), "classes/Main.class");
+        assert_eq!(std::fs::read_to_string(run.join("data/input.txt")).unwrap(), "data/input.txt");
+
+        // Programs that run their source keep it
+        executable.source = None;
+        let run = Tito::working_dir(dir.path(), &executable, std::iter::empty()).unwrap();
+        assert!(run.join("source.cpp").exists());
+
+        for name in ["../escaped", "data/../../escaped", "/tmp/escaped", "./data"] {
+            let result = Tito::working_dir(dir.path(), &executable, [fixture(name)].iter());
+            assert!(matches!(result, Err(Error::InvalidFixture(_))), "{}", name);
+        }
+        assert!(!dir.path().join("escaped").exists());
+    }
+
+    #[test]
+    fn players_only_read_their_part_of_a_match() {
+        let mut transcript = Transcript::new();
+        transcript.note_for(Audience::Seat(0), "referee > 0: your cards are 1 2".to_string());
+        transcript.note_for(Audience::Seat(1), "referee > 1: your cards are 3 4".to_string());
+        transcript.note_for(Audience::Seat(1), "1 ! thinking about 3".to_string());
+        transcript.note_for(Audience::Organizer, "referee ! seed 42".to_string());
+        transcript.note("1 timed out".to_string());
+        let lines = |seat| transcript.view(seat).lines().map(|line| line.split_once("] ").unwrap().1.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines(Some(0)), vec!["referee > 0: your cards are 1 2", "1 timed out"]);
+        assert_eq!(lines(Some(1)), vec!["referee > 1: your cards are 3 4", "1 ! thinking about 3", "1 timed out"]);
+        assert_eq!(lines(None).len(), 5);
+    }
+
+    #[test]
+    fn tools_and_generators_get_a_clean_environment() {
+        // Cargo gives the tests variables of its own, which the grader would otherwise pass on
+        assert!(std::env::var_os("CARGO_PKG_NAME").is_some());
+        let dir = TempDir::new("environment").unwrap();
+        let compiler = Tool::new("sh", false, vec!["-c".to_string(), "env > {pwd}/compiled".to_string()]);
+        let mut settings = LanguageSettings::new(Some(vec![compiler]), Tool::new("sh", false, vec!["-c".to_string(), "env".to_string()]), "sh");
+        settings.env.insert("HOME".to_string(), "{pwd}".to_string());
+        let tito = Tito::new().unwrap();
+        let executable = tito.compile("", &settings, dir.path()).unwrap();
+        let generated = tito.run_generator("env", &executable, &[]).unwrap();
+
+        for env in [std::fs::read_to_string(dir.path().join("compiled")).unwrap(), String::from_utf8(generated).unwrap()] {
+            let variables: HashMap<_, _> = env.lines().filter_map(|line| line.split_once('=')).collect();
+            assert_eq!(variables.get("LANG"), Some(&"C.UTF-8"));
+            assert_eq!(variables.get("HOME").copied(), Some(&*dir.path().to_string_lossy()));
+            assert!(variables.contains_key("PATH"));
+            assert!(!variables.contains_key("CARGO_PKG_NAME"));
+        }
+    }
+
+    #[test]
+    fn compilations_are_cached_unless_bypassed() {
+        let dir = TempDir::new("compilations").unwrap();
+        let runs = dir.path().join("runs");
+        // Every run of the compiler leaves a line in a file outside of the compilation
+        let compiler = Tool::new("sh", false, vec!["-c".to_string(), "echo run >> {runs}; cp {filename} {pwd}/built".to_string()]);
+        let mut settings = LanguageSettings::new(Some(vec![compiler]), Tool::new("cat", false, vec!["built".to_string()]), "txt");
+        settings.placeholders.insert("runs".to_string(), runs.to_string_lossy().into_owned());
+        let compile = |tito: &Tito, name: &str, source: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            tito.compile(source, &settings, &path).unwrap();
+            std::fs::read_to_string(path.join("built")).unwrap()
+        };
+        let compiler_runs = || std::fs::read_to_string(&runs).unwrap().lines().count();
+
+        let cached = Tito::new().unwrap().with_cache(Some(Cache::new(dir.path().join("cache"))));
+        assert_eq!(compile(&cached, "first", "a"), "a");
+        assert_eq!(compile(&cached, "second", "a"), "a");
+        assert_eq!(compiler_runs(), 1);
+        assert_eq!(compile(&cached, "third", "b"), "b");
+        assert_eq!(compiler_runs(), 2);
+
+        let bypassed = Tito::new().unwrap();
+        assert_eq!(compile(&bypassed, "fourth", "a"), "a");
+        assert_eq!(compiler_runs(), 3);
+    }
+
+    #[test]
+    fn languages_are_checked_before_building() {
+        let mut tito = Tito::new().unwrap();
+        let gather = |tito: &mut Tito, name: &str, configured: &HashMap<Language, LanguageSettings>| {
+            tito.gather_language_info(HashSet::from([Language::new(name)]), configured)
+        };
+        let mut configured = HashMap::new();
+        assert!(gather(&mut tito, "Shell", &configured).is_ok());
+        assert!(matches!(gather(&mut tito, "Cobol", &configured), Err(Error::UnknownLanguage(name)) if name == "Cobol"));
+
+        let mut settings = LanguageSettings::new(None, Tool::new("sh", false, vec!["{filename}".to_string(), "{flags}".to_string()]), "sh");
+        configured.insert(Language::new("Cobol"), settings.clone());
+        assert!(matches!(gather(&mut tito, "Cobol", &configured), Err(Error::InvalidLanguage(_, detail)) if detail.contains("{flags}")));
+
+        // Configured settings replace the ones of the presets
+        settings.placeholders.insert("flags".to_string(), "-e".to_string());
+        configured.insert(Language::new("Shell"), settings.clone());
+        assert!(gather(&mut tito, "Shell", &configured).is_ok());
+        settings.seccomp = Some("none".to_string());
+        configured.insert(Language::new("Shell"), settings.clone());
+        assert!(matches!(gather(&mut tito, "Shell", &configured), Err(Error::InvalidLanguage(name, _)) if name == "Shell"));
+
+        settings.seccomp = None;
+        settings.tool.utility = "tito-missing-interpreter".to_string();
+        configured.insert(Language::new("Cobol"), settings.clone());
+        assert!(matches!(gather(&mut tito, "Cobol", &configured), Err(Error::MissingTool(tool)) if tool == "tito-missing-interpreter"));
+        settings.tool = Tool::new("./{binary}", true, Vec::new());
+        settings.pre_tools = Some(vec![Tool::new("tito-missing-{binary}", false, Vec::new())]);
+        settings.placeholders.insert("binary".to_string(), "compiler".to_string());
+        configured.insert(Language::new("Cobol"), settings);
+        assert!(matches!(gather(&mut tito, "Cobol", &configured), Err(Error::MissingTool(tool)) if tool == "tito-missing-compiler"));
+    }
+
+    #[test]
+    fn generated_inputs_are_cached_unless_bypassed() {
+        let dir = TempDir::new("generators").unwrap();
+        let runs = dir.path().join("runs");
+        // Every run of the generator leaves a line in a file outside of its working directory
+        std::fs::write(dir.path().join("gen.sh"), format!("echo run >> {}\necho \"$@\"\n", runs.display())).unwrap();
+        std::fs::write(dir.path().join("solution.sh"), "cat\n").unwrap();
+        let settings = |commands: &[&str]| -> Settings {
+            let scenarios: Vec<_> = commands.iter().map(|command| serde_json::json!({"generator": command, "max_time": 1.0, "points": 1})).collect();
+            serde_json::from_value(serde_json::json!({
+                "proposals": {"echo": {
+                    "solution": dir.path().join("solution.sh"),
+                    "language": "Shell",
+                    "generators": {"gen": {"source": dir.path().join("gen.sh"), "language": "Shell"}},
+                    "scenarios": scenarios,
+                    "points": commands.len()
+                }}
+            })).unwrap()
+        };
+        let build = |tito: &mut Tito, commands: &[&str]| -> Vec<String> {
+            let arena = tito.build(settings(commands), dir.path()).unwrap();
+            arena.problems["echo"].scenarios.iter().map(|scenario| scenario.input.clone().unwrap()).collect()
+        };
+        let generator_runs = || std::fs::read_to_string(&runs).unwrap().lines().count();
+
+        let mut cached = Tito::new().unwrap().with_cache(Some(Cache::new(dir.path().join("cache"))));
+        assert_eq!(build(&mut cached, &["gen 1 2", "gen 3"]), vec!["1 2\n", "3\n"]);
+        assert_eq!(generator_runs(), 2);
+        assert_eq!(build(&mut cached, &["gen 3", "gen  1   2"]), vec!["3\n", "1 2\n"]);
+        assert_eq!(generator_runs(), 2);
+        assert_eq!(build(&mut cached, &["gen 1 2 3"]), vec!["1 2 3\n"]);
+        assert_eq!(generator_runs(), 3);
+
+        let mut bypassed = Tito::new().unwrap();
+        assert_eq!(build(&mut bypassed, &["gen 1 2"]), vec!["1 2\n"]);
+        assert_eq!(generator_runs(), 4);
+    }
+}
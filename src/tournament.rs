@@ -0,0 +1,345 @@
+extern crate serde;
+
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use crate::{Program, Protocol, Language, LanguageSettings, Evaluation};
+
+/// Rating every bot starts the tournament with
+const INITIAL_RATING: f64 = 1500.0;
+/// Maximum change of rating a bot can get from a single match
+const RATING_FACTOR: f64 = 32.0;
+
+/// Describes a tournament where the bots of the competitors play each other, mediated by a referee
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tournament {
+    /// Program that runs every match and decides the scores of the players
+    pub referee: Program,
+    /// Filename, without extension, of the bot searched for in the files of each competitor
+    pub filename: String,
+    /// Language the bots are written in
+    pub language: Language,
+    /// Number of bots in every match
+    #[serde(default = "Tournament::default_players")]
+    pub players: usize,
+    /// How the bots are matched in every round
+    pub format: Format,
+    /// Maximum time, in seconds, a whole match can last
+    pub max_time: f32,
+    /// Maximum time, in seconds, a bot can take to answer a message of the referee
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_time: Option<f32>,
+    /// Maximum allowed Ram of every bot, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ram: Option<u32>,
+    /// Points of a bye, which should be the score the referee gives for a win
+    #[serde(default = "Tournament::default_bye_points")]
+    pub bye_points: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language_settings: Option<HashMap<Language, LanguageSettings>>
+}
+
+/// Way the bots are matched against each other
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum Format {
+    /// Every group of bots plays once per round, changing seats between rounds
+    RoundRobin {
+        #[serde(default = "Tournament::default_rounds")]
+        rounds: usize
+    },
+    /// Every round groups bots with similar scores that did not meet yet
+    Swiss {
+        rounds: usize
+    }
+}
+
+/// Position of a bot in the tournament
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Standing {
+    pub id: String,
+    /// Sum of the scores given by the referee, byes included
+    pub points: f64,
+    /// Matches played, byes excluded
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Elo rating after the last match
+    pub rating: f64
+}
+
+/// Outcome of a single match
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Match {
+    /// Round the match belongs to, starting at 1
+    pub round: usize,
+    /// Ids of the bots, in the order of their seats
+    pub players: Vec<String>,
+    /// Scores given by the referee, in the same order as the players
+    pub scores: Vec<f64>,
+    /// Name of the file with the messages of the match, absent for byes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+    /// Names of the files with the part of the log each player can read, in the same order as the players
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_logs: Vec<String>,
+    /// Reason the match was void, in which case it does not count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+/// Final state of a tournament
+#[derive(Serialize, Deserialize)]
+pub struct TournamentResult {
+    /// Standings of the bots, the best one first
+    pub standings: Vec<Standing>,
+    pub matches: Vec<Match>,
+    /// Competitors whose bot could not take part, with the reason
+    pub excluded: HashMap<String, Evaluation>
+}
+
+impl Tournament {
+    fn default_players() -> usize {
+        2
+    }
+
+    fn default_rounds() -> usize {
+        1
+    }
+
+    fn default_bye_points() -> f64 {
+        1.0
+    }
+
+    /// Number of rounds of the tournament
+    pub fn rounds(&self) -> usize {
+        match self.format {
+            Format::RoundRobin{rounds} | Format::Swiss{rounds} => rounds
+        }
+    }
+
+    /// Checks that the tournament can be played, returning a description of the problem otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if self.players < 2 {
+            return Err("matches need at least 2 players".to_string());
+        }
+        if self.rounds() == 0 {
+            return Err("there has to be at least one round".to_string());
+        }
+        if self.max_time.is_nan() || self.max_time <= 0.0 || self.move_time.map(|move_time| move_time.is_nan() || move_time <= 0.0).unwrap_or(false) {
+            return Err("times have to be positive numbers".to_string());
+        }
+        if !self.bye_points.is_finite() {
+            return Err("the points of a bye have to be a number".to_string());
+        }
+        Ok(())
+    }
+
+    /// Generates an example tournament between two player bots written in python
+    pub fn example() -> Tournament {
+        let python = Language::new("Python3");
+        Tournament {
+            referee: Program{source: "./referee.py".to_string(), language: python.clone(), code: None, protocol: Protocol::Testlib, arguments: Vec::new()},
+            filename: "bot".to_string(),
+            language: python,
+            players: 2,
+            format: Format::RoundRobin{rounds: 2},
+            max_time: 60.0,
+            move_time: Some(1.0),
+            max_ram: None,
+            bye_points: 1.0,
+            language_settings: None
+        }
+    }
+}
+
+/// Standings of a tournament while it is being played, with the bots referred to by index
+pub(crate) struct Table {
+    pub standings: Vec<Standing>,
+    /// Pairs of bots, smaller index first, that already played each other
+    met: HashSet<(usize, usize)>,
+    /// Bots that already got a bye
+    byes: HashSet<usize>
+}
+
+impl Table {
+    pub fn new(ids: Vec<String>) -> Table {
+        Table {
+            standings: ids.into_iter().map(|id| Standing{id, points: 0.0, played: 0, wins: 0, draws: 0, losses: 0, rating: INITIAL_RATING}).collect(),
+            met: HashSet::new(),
+            byes: HashSet::new()
+        }
+    }
+
+    /// Indices of the bots, the best one first
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.standings.len()).collect();
+        ranking.sort_by(|a, b| {
+            let (a, b) = (&self.standings[*a], &self.standings[*b]);
+            b.points.total_cmp(&a.points).then(b.rating.total_cmp(&a.rating)).then(a.id.cmp(&b.id))
+        });
+        ranking
+    }
+
+    /// Adds the scores of a match. The best score wins, a shared best score is a draw, and ratings are updated
+    /// as if every player had played each of the others.
+    pub fn record(&mut self, players: &[usize], scores: &[f64]) {
+        let best = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let winners = scores.iter().filter(|score| **score == best).count();
+
+        let opponents = (players.len() - 1) as f64;
+        let deltas: Vec<f64> = players.iter().zip(scores).map(|(player, score)| {
+            players.iter().zip(scores).filter(|(other, _)| *other != player).map(|(other, other_score)| {
+                let expected = 1.0 / (1.0 + 10f64.powf((self.standings[*other].rating - self.standings[*player].rating) / 400.0));
+                let actual = if score > other_score { 1.0 } else if score == other_score { 0.5 } else { 0.0 };
+                RATING_FACTOR * (actual - expected) / opponents
+            }).sum()
+        }).collect();
+
+        for ((player, score), delta) in players.iter().zip(scores).zip(deltas) {
+            let standing = &mut self.standings[*player];
+            standing.points += score;
+            standing.played += 1;
+            standing.rating += delta;
+            if *score < best {
+                standing.losses += 1;
+            } else if winners > 1 {
+                standing.draws += 1;
+            } else {
+                standing.wins += 1;
+            }
+        }
+    }
+
+    /// Remembers that the bots were paired, even if their match did not count
+    pub fn meet(&mut self, players: &[usize]) {
+        for a in players {
+            for b in players {
+                if a < b {
+                    self.met.insert((*a, *b));
+                }
+            }
+        }
+    }
+
+    /// Gives a free win, worth the given points, to a bot left without a match
+    pub fn bye(&mut self, player: usize, points: f64) {
+        let standing = &mut self.standings[player];
+        standing.points += points;
+        standing.wins += 1;
+        self.byes.insert(player);
+    }
+
+    /// Groups the bots for the given round, starting at 0, and the bots that get a bye in it
+    pub fn pairings(&self, format: &Format, players: usize, round: usize) -> (Vec<Vec<usize>>, Vec<usize>) {
+        match format {
+            Format::RoundRobin{..} => {
+                let mut groups = Vec::new();
+                combinations(self.standings.len(), players, &mut Vec::new(), &mut groups);
+                // Seats rotate between rounds, so no bot always moves first
+                for group in groups.iter_mut() {
+                    group.rotate_left(round % players);
+                }
+                (groups, Vec::new())
+            },
+            Format::Swiss{..} => {
+                let mut ranking = self.ranking();
+
+                // The lowest ranked bots without a bye yet are the ones left out
+                let mut byes = Vec::new();
+                for _ in 0..ranking.len() % players {
+                    let idx = ranking.iter().rposition(|player| !self.byes.contains(player)).unwrap_or(ranking.len() - 1);
+                    byes.push(ranking.remove(idx));
+                }
+
+                let mut groups = Vec::new();
+                while !ranking.is_empty() {
+                    let mut group = vec![ranking.remove(0)];
+                    while group.len() < players {
+                        let fresh = ranking.iter().position(|candidate| group.iter().all(|player| {
+                            !self.met.contains(&(*player.min(candidate), *player.max(candidate)))
+                        }));
+                        group.push(ranking.remove(fresh.unwrap_or(0)));
+                    }
+                    groups.push(group);
+                }
+                (groups, byes)
+            }
+        }
+    }
+}
+
+/// Collects every group of `size` indices below `count`, in lexicographic order
+fn combinations(count: usize, size: usize, current: &mut Vec<usize>, groups: &mut Vec<Vec<usize>>) {
+    if current.len() == size {
+        groups.push(current.clone());
+        return;
+    }
+    let start = current.last().map(|last| last + 1).unwrap_or(0);
+    for idx in start..count {
+        current.push(idx);
+        combinations(count, size, current, groups);
+        current.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(bots: usize) -> Table {
+        Table::new((0..bots).map(|bot| format!("bot-{}", bot)).collect())
+    }
+
+    #[test]
+    fn round_robin_rotates_seats() {
+        let table = table(3);
+        let format = Format::RoundRobin{rounds: 2};
+        assert_eq!(table.pairings(&format, 2, 0), (vec![vec![0, 1], vec![0, 2], vec![1, 2]], Vec::new()));
+        assert_eq!(table.pairings(&format, 2, 1), (vec![vec![1, 0], vec![2, 0], vec![2, 1]], Vec::new()));
+        assert_eq!(table.pairings(&format, 3, 0).0, vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn swiss_avoids_rematches_and_repeated_byes() {
+        let mut table = table(5);
+        let format = Format::Swiss{rounds: 3};
+        let (groups, byes) = table.pairings(&format, 2, 0);
+        assert_eq!((groups.clone(), byes.clone()), (vec![vec![0, 1], vec![2, 3]], vec![4]));
+        for group in groups.iter() {
+            table.meet(group);
+            table.record(group, &[1.0, 0.0]);
+        }
+        table.bye(4, 1.0);
+
+        // Bots 0, 2 and 4 lead, and the last of the ranking without a bye sits out
+        let (groups, byes) = table.pairings(&format, 2, 1);
+        assert_eq!(byes, vec![3]);
+        assert_eq!(groups, vec![vec![0, 2], vec![4, 1]]);
+    }
+
+    #[test]
+    fn byes_are_worth_the_configured_points() {
+        let mut table = table(1);
+        table.bye(0, 3.0);
+        let standing = &table.standings[0];
+        assert_eq!((standing.points, standing.wins, standing.played), (3.0, 1, 0));
+    }
+
+    #[test]
+    fn elo_moves_ratings_between_players() {
+        let mut table = table(3);
+        table.record(&[0, 1], &[2.0, 1.0]);
+        let (winner, loser) = (&table.standings[0], &table.standings[1]);
+        assert_eq!((winner.rating, loser.rating), (INITIAL_RATING + RATING_FACTOR / 2.0, INITIAL_RATING - RATING_FACTOR / 2.0));
+        assert_eq!((winner.wins, loser.losses, winner.points), (1, 1, 2.0));
+
+        // Equal players drawing keep their ratings, and the upset of a weaker player is worth more
+        table.record(&[0, 2], &[0.0, 0.0]);
+        assert!(table.standings[0].rating < INITIAL_RATING + RATING_FACTOR / 2.0);
+        assert!(table.standings[2].rating > INITIAL_RATING);
+        assert_eq!((table.standings[0].draws, table.standings[2].draws), (1, 1));
+        let total: f64 = table.standings.iter().map(|standing| standing.rating).sum();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-9);
+    }
+}